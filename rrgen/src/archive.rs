@@ -0,0 +1,55 @@
+use std::{
+    io::{Cursor, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{Error, Result};
+
+/// The format of an archive built with [`crate::RRgen::archive`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// A gzipped tarball, `.tar.gz`
+    TarGz,
+    Zip,
+}
+
+/// A file to put in an archive: its relative path, content and mode
+pub type Entry<'a> = (String, &'a [u8], u32);
+
+/// Builds an archive of `entries` in memory
+pub fn build<'a>(
+    format: ArchiveFormat,
+    entries: impl IntoIterator<Item = Entry<'a>>,
+) -> Result<Vec<u8>> {
+    match format {
+        ArchiveFormat::TarGz => {
+            let mtime = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+            for (path, content, mode) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(mode);
+                header.set_mtime(mtime);
+                tar.append_data(&mut header, path, content)?;
+            }
+            Ok(tar.into_inner()?.finish()?)
+        }
+        ArchiveFormat::Zip => {
+            let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+            for (path, content, mode) in entries {
+                zip.start_file(path, SimpleFileOptions::default().unix_permissions(mode))
+                    .map_err(|err| Error::Any(Box::new(err)))?;
+                zip.write_all(content)?;
+            }
+            Ok(zip
+                .finish()
+                .map_err(|err| Error::Any(Box::new(err)))?
+                .into_inner())
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+/// The answer of a [`ConflictResolver`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Overwrite the existing file with the new content
+    Overwrite,
+    /// Keep the existing file
+    Skip,
+    /// Show the difference through the `Printer`, then ask again
+    Diff,
+    /// Overwrite this and every following conflicting file
+    OverwriteAll,
+    /// Keep this and every following conflicting file
+    SkipAll,
+    /// Stop generating, failing with `Error::Aborted`
+    Abort,
+}
+
+/// Decides what to do with a file that exists and differs from what is
+/// about to be generated, when the overwrite policy is `prompt`.
+///
+/// Once a resolver answers [`Resolution::OverwriteAll`] or
+/// [`Resolution::SkipAll`], it is not asked again by the same `RRgen`.
+pub trait ConflictResolver {
+    fn resolve(&self, file_to: &Path, existing: &str, generated: &str) -> Resolution;
+}
+
+/// The default resolver, which never overwrites
+pub struct NonInteractiveResolver {}
+impl ConflictResolver for NonInteractiveResolver {
+    fn resolve(&self, _file_to: &Path, _existing: &str, _generated: &str) -> Resolution {
+        Resolution::Skip
+    }
+}
+
+const HELP: &str = "\
+Y - yes, overwrite
+n - no, do not overwrite
+a - all, overwrite this and all others
+s - skip this and all others
+q - quit, abort
+d - diff, show the differences between the old and the new
+h - help, show this help";
+
+/// Asks on the terminal, with a prompt in the style of Rails' `Ynaqdh`.
+/// Keeps the existing file when stdin is closed.
+pub struct StdinResolver {}
+impl ConflictResolver for StdinResolver {
+    fn resolve(&self, file_to: &Path, _existing: &str, _generated: &str) -> Resolution {
+        loop {
+            print!(
+                "overwrite {}? (enter \"h\" for help) [Ynasqdh] ",
+                file_to.display()
+            );
+            let _ = io::stdout().flush();
+
+            let mut answer = String::new();
+            match io::stdin().lock().read_line(&mut answer) {
+                Ok(0) | Err(_) => return Resolution::Skip,
+                Ok(_) => {}
+            }
+            match answer.trim().to_lowercase().as_str() {
+                "" | "y" | "yes" => return Resolution::Overwrite,
+                "n" | "no" => return Resolution::Skip,
+                "a" | "all" => return Resolution::OverwriteAll,
+                "s" => return Resolution::SkipAll,
+                "q" | "quit" => return Resolution::Abort,
+                "d" | "diff" => return Resolution::Diff,
+                _ => println!("{HELP}"),
+            }
+        }
+    }
+}
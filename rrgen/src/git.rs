@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use git2::{Index, Repository, Signature, StatusOptions};
+
+use crate::Result;
+
+/// What to do with the git repository containing the working directory.
+/// Nothing is done when it is not inside a repository.
+#[derive(Debug, Clone, Default)]
+pub struct Git {
+    /// Refuse to generate when a file the template targets has uncommitted
+    /// changes, including untracked files
+    pub require_clean: bool,
+    /// Stage the files generation created, changed or removed
+    pub stage: bool,
+    /// Stage the files, then commit them with this message, rendered with the
+    /// template's vars. Only the files generation touched are committed;
+    /// anything else already staged stays staged.
+    pub commit: Option<String>,
+}
+
+/// Finds the repository containing `dir`
+pub fn open(dir: Option<&Path>) -> Option<Repository> {
+    Repository::discover(dir.unwrap_or_else(|| Path::new("."))).ok()
+}
+
+/// `path` relative to the repository's working tree, if it is inside it.
+/// `path` need not exist.
+fn repo_path(repo: &Repository, path: &Path) -> Option<PathBuf> {
+    let workdir = repo.workdir()?.canonicalize().ok()?;
+    let path = std::path::absolute(path).ok()?;
+
+    // canonicalize what exists of the path, so that both sides agree on
+    // symlinks such as a temp folder's
+    let mut existing = path.as_path();
+    let mut rest = vec![];
+    while !existing.exists() {
+        rest.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
+    let mut path = existing.canonicalize().ok()?;
+    path.extend(rest.iter().rev());
+    path.strip_prefix(&workdir).ok().map(Path::to_path_buf)
+}
+
+/// Lists the files with uncommitted changes at or under `paths`
+pub fn dirty(repo: &Repository, paths: &[PathBuf]) -> Result<Vec<String>> {
+    let targets = paths
+        .iter()
+        .filter_map(|path| repo_path(repo, path))
+        .collect::<Vec<_>>();
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    Ok(repo
+        .statuses(Some(&mut options))?
+        .iter()
+        .filter(|entry| !entry.status().is_empty())
+        .filter_map(|entry| entry.path().map(PathBuf::from))
+        .filter(|path| targets.iter().any(|target| path.starts_with(target)))
+        .map(|path| path.display().to_string())
+        .collect())
+}
+
+/// Stages `paths`, removing the ones that no longer exist from the index
+pub fn stage(repo: &Repository, paths: &[PathBuf]) -> Result<()> {
+    let mut index = repo.index()?;
+    for path in paths {
+        let Some(file) = repo_path(repo, path) else {
+            continue;
+        };
+        if path.exists() {
+            index.add_path(&file)?;
+        } else {
+            index.remove_path(&file)?;
+        }
+    }
+    index.write()?;
+    Ok(())
+}
+
+/// The signature commits are made with, from the repository's config
+pub fn signature(repo: &Repository) -> Result<Signature<'static>> {
+    Ok(repo.signature()?)
+}
+
+/// Commits the staged state of `paths` on top of `HEAD`. Anything else in
+/// the index is left staged rather than committed.
+pub fn commit(repo: &Repository, message: &str, paths: &[PathBuf]) -> Result<()> {
+    let signature = signature(repo)?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+
+    let mut index = Index::new()?;
+    if let Some(parent) = &parent {
+        index.read_tree(&parent.tree()?)?;
+    }
+    let staged = repo.index()?;
+    for path in paths {
+        let Some(file) = repo_path(repo, path) else {
+            continue;
+        };
+        if let Some(entry) = staged.get_path(&file, 0) {
+            index.add(&entry)?;
+        } else if index.get_path(&file, 0).is_some() {
+            index.remove_path(&file)?;
+        }
+    }
+    let tree = repo.find_tree(index.write_tree_to(repo)?)?;
+
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parent.iter().collect::<Vec<_>>(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::{path::Path, process::Command};
+
+use crate::{Error, Result};
+
+/// The captured output of a `before` or `after` hook command
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub command: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs `command` through the platform shell with `cwd` as the working
+/// directory.
+///
+/// # Errors
+///
+/// Returns an error if the command cannot be started or exits unsuccessfully
+pub fn run(command: &str, cwd: Option<&Path>) -> Result<CommandOutput> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    if let Some(cwd) = cwd {
+        cmd.current_dir(cwd);
+    }
+
+    let output = cmd.output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() {
+        return Err(Error::Message(format!(
+            "command `{command}` failed with {}: {}",
+            output.status,
+            stderr.trim_end()
+        )));
+    }
+    Ok(CommandOutput {
+        command: command.to_string(),
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr,
+    })
+}
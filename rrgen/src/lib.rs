@@ -1,10 +1,34 @@
-use std::path::{Path, PathBuf};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tera::{Context, Tera};
 
+#[cfg(feature = "archive")]
+mod archive;
+mod conflict;
+#[cfg(feature = "git")]
+mod git;
+mod hooks;
+mod manifest;
+mod merge;
+mod overlay;
 mod tera_filters;
+mod variables;
+
+#[cfg(feature = "archive")]
+pub use archive::ArchiveFormat;
+pub use conflict::{ConflictResolver, NonInteractiveResolver, Resolution, StdinResolver};
+#[cfg(feature = "git")]
+pub use git::Git;
+pub use hooks::CommandOutput;
+pub use manifest::{BackupEntry, FileEntry, FileRecord, InjectionEntry, Manifest, Run, RunKind};
+use overlay::Overlay;
 pub trait FsDriver {
     /// Write a file
     ///
@@ -21,6 +45,100 @@ pub trait FsDriver {
     fn read_file(&self, path: &Path) -> Result<String>;
 
     fn exists(&self, path: &Path) -> bool;
+
+    /// Write a file byte for byte, without requiring it to be UTF-8
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails. The default
+    /// implementation fails for content that is not UTF-8.
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let content = std::str::from_utf8(content).map_err(|err| Error::Any(Box::new(err)))?;
+        self.write_file(path, content)
+    }
+
+    /// Read a file byte for byte, without requiring it to be UTF-8
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails. The default
+    /// implementation fails for content that is not UTF-8.
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>> {
+        Ok(self.read_file(path)?.into_bytes())
+    }
+
+    /// Remove a file
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails. The default
+    /// implementation always fails.
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        Err(Error::Message(format!(
+            "cannot remove {}: not supported by this driver",
+            path.display()
+        )))
+    }
+
+    /// Move a file to a new path
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails. The default
+    /// implementation always fails.
+    fn rename_file(&self, from: &Path, to: &Path) -> Result<()> {
+        Err(Error::Message(format!(
+            "cannot move {} to {}: not supported by this driver",
+            from.display(),
+            to.display()
+        )))
+    }
+
+    /// List the entries of a folder
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails. The default
+    /// implementation always fails.
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        Err(Error::Message(format!(
+            "cannot list {}: not supported by this driver",
+            path.display()
+        )))
+    }
+
+    /// Remove a folder and everything in it
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails. The default
+    /// implementation always fails.
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        Err(Error::Message(format!(
+            "cannot remove {}: not supported by this driver",
+            path.display()
+        )))
+    }
+
+    /// Set the permission mode of a file, such as `0o755`. Drivers without a
+    /// notion of permissions can ignore it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails
+    fn set_mode(&self, _path: &Path, _mode: u32) -> Result<()> {
+        Ok(())
+    }
+
+    /// Get the permission mode of a file, if the driver has a notion of
+    /// permissions
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails
+    fn mode(&self, _path: &Path) -> Result<Option<u32>> {
+        Ok(None)
+    }
 }
 
 pub struct RealFsDriver {}
@@ -37,9 +155,58 @@ impl FsDriver for RealFsDriver {
         Ok(fs_err::read_to_string(path)?)
     }
 
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let dir = path.parent().expect("cannot get folder");
+        if !dir.exists() {
+            fs_err::create_dir_all(dir)?;
+        }
+        Ok(fs_err::write(path, content)?)
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>> {
+        Ok(fs_err::read(path)?)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        Ok(fs_err::remove_file(path)?)
+    }
+
+    fn rename_file(&self, from: &Path, to: &Path) -> Result<()> {
+        let dir = to.parent().expect("cannot get folder");
+        if !dir.exists() {
+            fs_err::create_dir_all(dir)?;
+        }
+        Ok(fs_err::rename(from, to)?)
+    }
+
     fn exists(&self, path: &Path) -> bool {
         path.exists()
     }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        fs_err::read_dir(path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect()
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        Ok(fs_err::remove_dir_all(path)?)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(fs_err::set_permissions(
+            path,
+            std::fs::Permissions::from_mode(mode),
+        )?)
+    }
+
+    #[cfg(unix)]
+    fn mode(&self, path: &Path) -> Result<Option<u32>> {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(Some(fs_err::metadata(path)?.permissions().mode() & 0o7777))
+    }
 }
 
 pub trait Printer {
@@ -47,6 +214,29 @@ pub trait Printer {
     fn skip_exists(&self, file_to: &Path);
     fn add_file(&self, file_to: &Path);
     fn injected(&self, file_to: &Path);
+    /// Called when a template or an injection is skipped, with the reason
+    fn skipped(&self, _file_to: &Path, _reason: &str) {}
+    /// Called when a file is deleted
+    fn removed(&self, _file_to: &Path) {}
+    /// Called when the content of an injection is removed from a file
+    fn reverted(&self, _file_to: &Path, _content: &str) {}
+    /// Called when a file is moved
+    fn moved(&self, _file_from: &Path, _file_to: &Path) {}
+    /// Called before a hook command runs
+    fn run_command(&self, _command: &str) {}
+    /// Called when a hook command is not run, with the reason
+    fn skip_command(&self, _command: &str, _reason: &str) {}
+    /// Called when new content is merged into a modified file, with the
+    /// number of conflicts left to resolve
+    fn merged(&self, _file_to: &Path, _conflicts: usize) {}
+    /// Called when a file is restored from a backup
+    fn restored(&self, _file_to: &Path) {}
+    /// Called when the previous content of a file is backed up, with where
+    /// the backup was written
+    fn backed_up(&self, _file_to: &Path, _backup: &Path) {}
+    /// Called when a `ConflictResolver` asks to see what would change, with
+    /// a unified diff from the existing file to the generated one
+    fn diff(&self, _file_to: &Path, _diff: &str) {}
 }
 pub struct ConsolePrinter {}
 impl Printer for ConsolePrinter {
@@ -65,30 +255,254 @@ impl Printer for ConsolePrinter {
     fn skip_exists(&self, file_to: &Path) {
         println!("skipped (exists): {file_to:?}");
     }
+
+    fn skipped(&self, file_to: &Path, reason: &str) {
+        println!("skipped ({reason}): {file_to:?}");
+    }
+
+    fn removed(&self, file_to: &Path) {
+        println!("removed: {file_to:?}");
+    }
+
+    fn moved(&self, file_from: &Path, file_to: &Path) {
+        println!("moved: {file_from:?} -> {file_to:?}");
+    }
+
+    fn reverted(&self, file_to: &Path, content: &str) {
+        println!("reverted: {file_to:?}");
+        for line in content.lines() {
+            println!("  - {line}");
+        }
+    }
+
+    fn run_command(&self, command: &str) {
+        println!("run: {command}");
+    }
+
+    fn skip_command(&self, command: &str, reason: &str) {
+        println!("skipped ({reason}): {command}");
+    }
+
+    fn restored(&self, file_to: &Path) {
+        println!("restored: {file_to:?}");
+    }
+
+    fn backed_up(&self, file_to: &Path, backup: &Path) {
+        println!("backed up: {file_to:?} -> {backup:?}");
+    }
+
+    fn diff(&self, file_to: &Path, diff: &str) {
+        println!("diff: {file_to:?}");
+        print!("{diff}");
+    }
+
+    fn merged(&self, file_to: &Path, conflicts: usize) {
+        if conflicts == 0 {
+            println!("merged: {file_to:?}");
+        } else {
+            println!("conflicts ({conflicts}): {file_to:?}");
+        }
+    }
+}
+
+/// A named injection strategy, selected from an injection with `strategy: <name>`.
+///
+/// Strategies receive the rendered injection config, which holds any extra
+/// keys given on the injection besides the built-in ones.
+pub trait InjectionStrategy {
+    /// Compute the new content of the injection target
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails
+    fn inject(
+        &self,
+        file_content: &str,
+        content: &str,
+        config: &serde_json::Map<String, serde_json::Value>,
+        vars: &serde_json::Value,
+    ) -> Result<String>;
+}
+
+/// What to do when the file a template generates already exists
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Overwrite {
+    /// Always overwrite the existing file
+    Always,
+    /// Never overwrite the existing file, skipping the template
+    Never,
+    /// Overwrite only if the file still matches what rrgen last generated,
+    /// according to the manifest
+    IfUnchanged,
+    /// Overwrite unless the manifest shows the file was modified since rrgen
+    /// last generated it. Files the manifest has no record of, or any file
+    /// when no manifest is kept, are overwritten. This is the default.
+    UnlessModified,
+    /// Ask the [`ConflictResolver`]
+    Prompt,
+    /// Three-way merge the newly generated content into a file modified since
+    /// rrgen last generated it, using the previously generated content kept
+    /// with the manifest as the base. Conflicting changes are written between
+    /// git-style conflict markers.
+    Merge,
+}
+
+/// Where to save the previous content of files before they are overwritten,
+/// deleted, or rewritten by an injection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backup {
+    /// Next to the file, as `file.rs.orig`, keeping only the latest
+    Sibling,
+    /// In a folder per run, as `<folder>/<run-id>/file.rs`, with the folder
+    /// relative to the working directory
+    Dir(PathBuf),
+}
+
+/// Reads a file mode written in octal. YAML hands over the literal as
+/// written, so `0755`, `755`, `0o755` and `"0755"` all read as octal. TOML
+/// and JSON only hand over the number's value, which cannot tell `755` from
+/// `0o1363`, so a number whose decimal digits could also be octal digits is
+/// refused rather than silently misread. Strings are always unambiguous.
+fn deserialize_mode<'de, D>(deserializer: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct ModeVisitor;
+
+    impl serde::de::Visitor<'_> for ModeVisitor {
+        type Value = u32;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a file mode in octal, such as \"0755\" or 0o755")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, value: &str) -> std::result::Result<u32, E> {
+            let digits = value
+                .strip_prefix("0o")
+                .or_else(|| value.strip_prefix("0O"))
+                .unwrap_or(value);
+            u32::from_str_radix(digits, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or_else(|| E::custom(format!("invalid file mode: {value}")))
+        }
+
+        fn visit_u64<E: serde::de::Error>(self, value: u64) -> std::result::Result<u32, E> {
+            if value.to_string().bytes().all(|digit| digit <= b'7') {
+                return Err(E::custom(format!(
+                    "ambiguous file mode: {value}; write it as a string, \"{value:o}\" for \
+                     0o{value:o} or \"{value}\" for 0o{value}"
+                )));
+            }
+            u32::try_from(value)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or_else(|| E::custom(format!("invalid file mode: 0o{value:o}")))
+        }
+
+        fn visit_i64<E: serde::de::Error>(self, value: i64) -> std::result::Result<u32, E> {
+            u64::try_from(value)
+                .map_err(|_| E::custom(format!("invalid file mode: {value}")))
+                .and_then(|value| self.visit_u64(value))
+        }
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for OptionVisitor {
+        type Value = Option<u32>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            ModeVisitor.expecting(formatter)
+        }
+
+        fn visit_none<E: serde::de::Error>(self) -> std::result::Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> std::result::Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: serde::Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> std::result::Result<Self::Value, D::Error> {
+            // asking for a string is what makes YAML hand over the literal
+            deserializer.deserialize_str(ModeVisitor).map(Some)
+        }
+    }
+
+    deserializer.deserialize_option(OptionVisitor)
 }
 
 #[derive(Deserialize, Debug, Default)]
 struct FrontMatter {
-    to: String,
+    #[serde(default)]
+    to: Option<String>,
 
     #[serde(default)]
     skip_exists: bool,
 
+    #[serde(default)]
+    overwrite: Option<Overwrite>,
+
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_mode")]
+    mode: Option<u32>,
+
+    #[serde(default)]
+    copy_from: Option<String>,
+
+    #[serde(default)]
+    delete: Vec<String>,
+
+    #[serde(rename = "move")]
+    #[serde(default)]
+    moves: Vec<Move>,
+
+    #[serde(default)]
+    before: Vec<String>,
+
+    #[serde(default)]
+    after: Vec<String>,
+
     #[serde(default)]
     skip_glob: Option<String>,
 
     #[serde(default)]
     message: Option<String>,
 
+    #[serde(default)]
+    when: Option<String>,
+
+    #[serde(default)]
+    unless: Option<String>,
+
     #[serde(default)]
     injections: Option<Vec<Injection>>,
+
+    #[serde(default)]
+    vars: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Move {
+    from: String,
+    to: String,
 }
 
 #[derive(Deserialize, Debug, Default)]
 struct Injection {
     into: String,
+
+    #[serde(default)]
     content: String,
 
+    #[serde(default)]
+    content_from: Option<String>,
+
     #[serde(with = "serde_regex")]
     #[serde(default)]
     skip_if: Option<Regex>,
@@ -118,6 +532,26 @@ struct Injection {
 
     #[serde(default)]
     append: bool,
+
+    #[serde(default)]
+    strategy: Option<String>,
+
+    #[serde(default)]
+    when: Option<String>,
+
+    #[serde(default)]
+    unless: Option<String>,
+
+    #[serde(flatten)]
+    config: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Injection {
+    /// Whether the injection places its content towards the end of the file,
+    /// where it is looked for again by `destroy` and the journal
+    fn towards_end(&self) -> bool {
+        self.append || self.before_last.is_some() || self.after_last.is_some()
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -133,33 +567,396 @@ pub enum Error {
     #[error(transparent)]
     YAML(#[from] serde_yaml::Error),
     #[error(transparent)]
+    TOML(#[from] toml::de::Error),
+    #[error(transparent)]
     Glob(#[from] glob::PatternError),
+    #[error("invalid template variables: {}", .0.join("; "))]
+    InvalidVars(Vec<String>),
+    #[error("generation aborted")]
+    Aborted,
+    #[cfg(feature = "git")]
+    #[error(transparent)]
+    Git(#[from] git2::Error),
     #[error(transparent)]
     Any(Box<dyn std::error::Error + Send + Sync>),
 }
-type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// What generation did, or would have done, to a file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    Created,
+    Overwritten,
+    Injected,
+    Deleted,
+    Moved,
+    /// the content of an injection was removed
+    Reverted,
+    /// new content was merged into a modified file
+    Merged,
+    /// new content was merged into a modified file, leaving conflict markers
+    Conflicted,
+    /// the previous content of a file was restored from its backup
+    Restored,
+}
+
+/// A file touched by generation
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub path: PathBuf,
+    /// the original path of a moved file
+    pub from: Option<PathBuf>,
+    pub kind: ActionKind,
+    /// why the action was skipped, if it was
+    pub skipped: Option<String>,
+    /// size of the written file, or of the injected content
+    pub bytes: usize,
+    /// lines of the written file, or of the injected content
+    pub lines: usize,
+}
+
+impl Action {
+    fn done(path: &Path, kind: ActionKind, content: &[u8]) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            from: None,
+            kind,
+            skipped: None,
+            bytes: content.len(),
+            lines: std::str::from_utf8(content).map_or(0, |text| text.lines().count()),
+        }
+    }
+
+    fn skipped(path: &Path, kind: ActionKind, reason: &str) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            from: None,
+            kind,
+            skipped: Some(reason.to_string()),
+            bytes: 0,
+            lines: 0,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum GenResult {
-    Skipped,
-    Generated { message: Option<String> },
+    Skipped {
+        /// what was skipped, each with the reason why
+        actions: Vec<Action>,
+        /// output of the `before` hook commands that ran before the template
+        /// turned out to be skipped
+        commands: Vec<CommandOutput>,
+    },
+    Generated {
+        message: Option<String>,
+        /// every file created, overwritten or injected into, including
+        /// skipped ones
+        actions: Vec<Action>,
+        /// output of the `before` and `after` hook commands that ran
+        commands: Vec<CommandOutput>,
+    },
+}
+
+/// The content of a generated file
+enum Content {
+    /// Rendered from the template body
+    Text(String),
+    /// Copied verbatim with `copy_from`
+    Bytes(Vec<u8>),
+}
+
+impl Content {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(text) => text.as_bytes(),
+            Self::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+/// A file to be generated
+struct Output {
+    path: PathBuf,
+    /// the path as given in the template, which keys the manifest
+    file: String,
+    content: Content,
+}
+
+/// Resolves a path given in a template, such as `content_from`, against the
+/// template's folder.
+fn template_path(template_dir: Option<&Path>, path: &str) -> PathBuf {
+    template_dir.map_or_else(|| PathBuf::from(path), |dir| dir.join(path))
+}
+
+/// Lists the files to copy verbatim from `source`, either a single file or a
+/// folder copied recursively into `path_to`.
+fn copy_outputs(source: &Path, path_to: &Path, to: &str) -> Result<Vec<Output>> {
+    if !source.is_dir() {
+        return Ok(vec![Output {
+            path: path_to.to_path_buf(),
+            file: to.to_string(),
+            content: Content::Bytes(fs_err::read(source)?),
+        }]);
+    }
+
+    let mut outputs = Vec::new();
+    copy_dir(source, Path::new(""), &mut |relative, content| {
+        outputs.push(Output {
+            path: path_to.join(relative),
+            file: Path::new(to).join(relative).to_string_lossy().to_string(),
+            content: Content::Bytes(content),
+        });
+    })?;
+    Ok(outputs)
+}
+
+/// Walks the folder `dir`, handing every file under it to `f` with its path
+/// relative to the folder being copied, in a stable order
+fn copy_dir(dir: &Path, relative: &Path, f: &mut impl FnMut(&Path, Vec<u8>)) -> Result<()> {
+    let mut entries = fs_err::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(fs_err::DirEntry::file_name);
+    for entry in entries {
+        let path = entry.path();
+        let relative = relative.join(entry.file_name());
+        if path.is_dir() {
+            copy_dir(&path, &relative, f)?;
+        } else if path.is_file() {
+            f(&relative, fs_err::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// Lists the files a template generates: its rendered body, or the files it
+/// copies verbatim.
+fn outputs(
+    frontmatter: &FrontMatter,
+    path_to: Option<PathBuf>,
+    template_dir: Option<&Path>,
+    render_body: impl FnOnce() -> tera::Result<String>,
+) -> Result<Vec<Output>> {
+    Ok(match (&frontmatter.to, path_to, &frontmatter.copy_from) {
+        (Some(to), Some(path_to), Some(copy_from)) => {
+            copy_outputs(&template_path(template_dir, copy_from), &path_to, to)?
+        }
+        (Some(to), Some(path_to), None) => vec![Output {
+            path: path_to,
+            file: to.clone(),
+            content: Content::Text(render_body()?),
+        }],
+        (None, _, Some(_)) => {
+            return Err(Error::Message(
+                "cannot copy_from without a `to` destination".to_string(),
+            ))
+        }
+        // an injection-only template
+        _ => vec![],
+    })
+}
+
+/// Renders the content of an injection, either inline or from the
+/// `content_from` partial.
+fn injection_content(
+    tera: &mut Tera,
+    context: &Context,
+    template_dir: Option<&Path>,
+    injection: &Injection,
+) -> Result<String> {
+    let Some(content_from) = &injection.content_from else {
+        return Ok(injection.content.clone());
+    };
+    let source = fs_err::read_to_string(template_path(template_dir, content_from))?;
+    let rendered = tera.render_str(&source, context)?;
+    Ok(rendered.strip_suffix('\n').unwrap_or(&rendered).to_string())
+}
+
+/// Finds the lines of `content` in `file_content`, where they appear as a
+/// contiguous block of whole lines, searching from the end when `from_end`.
+/// Returns the index of the block's first line.
+fn find_block(file_content: &str, content: &str, from_end: bool) -> Option<usize> {
+    let lines = file_content.lines().collect::<Vec<_>>();
+    let block = content.lines().collect::<Vec<_>>();
+    if block.is_empty() || block.len() > lines.len() {
+        return None;
+    }
+    let mut positions =
+        (0..=lines.len() - block.len()).filter(|&i| lines[i..i + block.len()] == block[..]);
+    if from_end {
+        positions.next_back()
+    } else {
+        positions.next()
+    }
+}
+
+/// Finds the lines of `content` in `file_content` like [`find_block`], taking
+/// the occurrence nearest to the 1-based `line` it was last seen at.
+/// Returns the index of the block's first line.
+fn locate_block(file_content: &str, content: &str, line: Option<usize>) -> Option<usize> {
+    let lines = file_content.lines().collect::<Vec<_>>();
+    let block = content.lines().collect::<Vec<_>>();
+    if block.is_empty() || block.len() > lines.len() {
+        return None;
+    }
+    let hint = line.map_or(0, |line| line.saturating_sub(1));
+    (0..=lines.len() - block.len())
+        .filter(|&i| lines[i..i + block.len()] == block[..])
+        .min_by_key(|&i| i.abs_diff(hint))
+}
+
+/// Removes the block of lines found by [`find_block`] from `file_content`.
+fn remove_block(file_content: &str, content: &str, from_end: bool) -> Option<String> {
+    let pos = find_block(file_content, content, from_end)?;
+    Some(remove_lines(file_content, pos, content.lines().count()))
+}
+
+/// Removes `count` lines starting at the line index `pos`
+fn remove_lines(file_content: &str, pos: usize, count: usize) -> String {
+    let mut lines = file_content.lines().collect::<Vec<_>>();
+    lines.drain(pos..pos + count);
+
+    let mut new_content = lines.join("\n");
+    if file_content.ends_with('\n') && !new_content.is_empty() {
+        new_content.push('\n');
+    }
+    new_content
+}
+
+/// `file` with any root or `..` dropped, for storing a copy of it inside
+/// another folder
+fn normal_path(file: &str) -> PathBuf {
+    Path::new(file)
+        .components()
+        .filter(|component| matches!(component, std::path::Component::Normal(_)))
+        .collect()
+}
+
+/// How a run's template is identified in the journal: its path, or a hash of
+/// its content when generated from a string
+fn template_id(input: &str, template_file: Option<&Path>) -> String {
+    template_file.map_or_else(
+        || format!("sha256:{}", manifest::hash(input.as_bytes())),
+        |path| path.display().to_string(),
+    )
+}
+
+/// Evaluates the `when` and `unless` expressions of a template or an
+/// injection, returning the reason to skip it if any.
+fn condition_skip_reason(
+    tera: &mut Tera,
+    context: &Context,
+    when: Option<&str>,
+    unless: Option<&str>,
+) -> Result<Option<String>> {
+    let mut eval = |expr: &str| -> Result<bool> {
+        let rendered = tera.render_str(&format!("{{% if {expr} %}}true{{% endif %}}"), context)?;
+        Ok(rendered == "true")
+    };
+    if let Some(when) = when {
+        if !eval(when)? {
+            return Ok(Some(format!("when: `{when}` is false")));
+        }
+    }
+    if let Some(unless) = unless {
+        if eval(unless)? {
+            return Ok(Some(format!("unless: `{unless}` is true")));
+        }
+    }
+    Ok(None)
+}
+
+/// The format of a template's frontmatter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// YAML, optionally opened with a `---` line, and closed by one
+    Yaml,
+    /// TOML, between `+++` lines
+    Toml,
+    /// a JSON object, closed by a line made of `}` or followed by a `---` line
+    Json,
+}
+
+impl Format {
+    fn parse<T: serde::de::DeserializeOwned>(self, input: &str) -> Result<T> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(input)?,
+            Self::Toml => toml::from_str(input)?,
+            Self::Json => serde_json::from_str(input)?,
+        })
+    }
 }
 
-fn parse_template(input: &str) -> Result<(FrontMatter, String)> {
+/// Splits a template into its frontmatter and body, before any rendering
+/// takes place.
+fn split_template(input: &str) -> Result<(Format, String, String)> {
     // normalize line endings
     let input = input.replace("\r\n", "\n");
 
-    let (fm, body) = input.split_once("---\n").ok_or_else(|| {
-        Error::Message("cannot split document to frontmatter and body".to_string())
-    })?;
-    let frontmatter: FrontMatter = serde_yaml::from_str(fm)?;
-    Ok((frontmatter, body.to_string()))
+    let first = input.split_inclusive('\n').next().unwrap_or_default();
+    let (format, start) = match first.trim_end() {
+        "+++" => (Format::Toml, first.len()),
+        "---" => (Format::Yaml, first.len()),
+        // `{%`, `{{` and `{#` open Tera tags rather than a JSON object
+        line if line.starts_with('{') && !line[1..].starts_with(['%', '{', '#']) => {
+            (Format::Json, 0)
+        }
+        _ => (Format::Yaml, 0),
+    };
+
+    let mut offset = start;
+    for line in input[start..].split_inclusive('\n') {
+        let end = offset + line.len();
+        let (frontmatter, body) = match (format, line.trim_end()) {
+            (Format::Yaml, "---") | (Format::Toml, "+++") | (Format::Json, "---") => {
+                (&input[start..offset], &input[end..])
+            }
+            (Format::Json, "}") => {
+                let body = &input[end..];
+                (
+                    &input[start..end],
+                    body.strip_prefix("---\n").unwrap_or(body),
+                )
+            }
+            _ => {
+                offset = end;
+                continue;
+            }
+        };
+        return Ok((format, frontmatter.to_string(), body.to_string()));
+    }
+    Err(Error::Message(
+        "cannot split document to frontmatter and body".to_string(),
+    ))
+}
+
+/// A template whose frontmatter has been rendered and parsed, ready for
+/// its body and injections to be rendered.
+struct Prepared {
+    frontmatter: FrontMatter,
+    body: String,
+    /// the caller's vars, with declared defaults and the frontmatter's
+    /// computed `vars`
+    vars: serde_json::Value,
+    tera: Tera,
+    context: Context,
 }
+
 pub struct RRgen {
     working_dir: Option<PathBuf>,
-    fs: Box<dyn FsDriver>,
+    fs: Overlay,
     printer: Box<dyn Printer>,
     template_engine: Tera,
+    injection_strategies: HashMap<String, Box<dyn InjectionStrategy>>,
+    overwrite: Option<Overwrite>,
+    conflict_resolver: Box<dyn ConflictResolver>,
+    /// an `OverwriteAll` or `SkipAll` answer, applied to every later conflict
+    resolve_all: Cell<Option<Resolution>>,
+    manifest: Option<PathBuf>,
+    backup: Option<Backup>,
+    #[cfg(feature = "git")]
+    git: Option<Git>,
+    allow_commands: bool,
 }
 
 impl Default for RRgen {
@@ -168,9 +965,18 @@ impl Default for RRgen {
         tera_filters::register_all(&mut tera);
         Self {
             working_dir: None,
-            fs: Box::new(RealFsDriver {}),
+            fs: Overlay::new(Box::new(RealFsDriver {})),
             printer: Box::new(ConsolePrinter {}),
             template_engine: tera,
+            injection_strategies: HashMap::new(),
+            overwrite: None,
+            conflict_resolver: Box::new(NonInteractiveResolver {}),
+            resolve_all: Cell::new(None),
+            manifest: None,
+            backup: None,
+            #[cfg(feature = "git")]
+            git: None,
+            allow_commands: false,
         }
     }
 }
@@ -212,48 +1018,1426 @@ impl RRgen {
         }
     }
 
-    /// Generate from a template contained in `input`
+    /// Registers a custom injection strategy under `name`.
     ///
-    /// # Errors
+    /// ```rust
+    /// use rrgen::{InjectionStrategy, RRgen, Result};
     ///
-    /// This function will return an error if operation fails
-    pub fn generate(&self, input: &str, vars: &serde_json::Value) -> Result<GenResult> {
-        let mut tera: Tera = self.template_engine.clone();
-        let rendered = tera.render_str(input, &Context::from_serialize(vars.clone())?)?;
-        let (frontmatter, body) = parse_template(&rendered)?;
+    /// struct Upcase;
+    /// impl InjectionStrategy for Upcase {
+    ///     fn inject(
+    ///         &self,
+    ///         file_content: &str,
+    ///         _content: &str,
+    ///         _config: &serde_json::Map<String, serde_json::Value>,
+    ///         _vars: &serde_json::Value,
+    ///     ) -> Result<String> {
+    ///         Ok(file_content.to_uppercase())
+    ///     }
+    /// }
+    ///
+    /// let rgen = RRgen::default().add_injection_strategy("upcase", Upcase);
+    ///
+    /// ```
+    #[must_use]
+    pub fn add_injection_strategy<S: InjectionStrategy + 'static>(
+        mut self,
+        name: &str,
+        strategy: S,
+    ) -> Self {
+        self.injection_strategies
+            .insert(name.to_string(), Box::new(strategy));
+        self
+    }
 
-        let path_to = if let Some(working_dir) = &self.working_dir {
-            working_dir.join(frontmatter.to)
+    /// Sets the overwrite policy for every template, taking precedence over
+    /// the templates' own `overwrite` and `skip_exists`.
+    ///
+    /// ```rust
+    /// use rrgen::{Overwrite, RRgen};
+    ///
+    /// // e.g. for a `--force` flag
+    /// let rgen = RRgen::default().with_overwrite(Overwrite::Always);
+    ///
+    /// ```
+    #[must_use]
+    pub fn with_overwrite(self, overwrite: Overwrite) -> Self {
+        Self {
+            overwrite: Some(overwrite),
+            ..self
+        }
+    }
+
+    /// Sets the resolver consulted by the `prompt` overwrite policy.
+    ///
+    /// ```rust
+    /// use rrgen::{Overwrite, RRgen, StdinResolver};
+    ///
+    /// let rgen = RRgen::default()
+    ///     .with_overwrite(Overwrite::Prompt)
+    ///     .with_conflict_resolver(StdinResolver {});
+    ///
+    /// ```
+    #[must_use]
+    pub fn with_conflict_resolver<R: ConflictResolver + 'static>(self, resolver: R) -> Self {
+        Self {
+            conflict_resolver: Box::new(resolver),
+            ..self
+        }
+    }
+
+    /// Keeps a manifest of generated files at `path`, relative to the working
+    /// directory. The manifest is what the `unless_modified`, `if_unchanged`
+    /// and `merge` overwrite policies compare against, and holds a journal of
+    /// every generate and destroy run: the template, its vars, and the files
+    /// and injections it touched. Without a manifest, hand edits cannot be
+    /// detected, so `unless_modified` overwrites existing files.
+    ///
+    /// ```rust
+    /// use rrgen::RRgen;
+    ///
+    /// let rgen = RRgen::default().with_manifest(".rrgen/manifest.json");
+    ///
+    /// ```
+    #[must_use]
+    pub fn with_manifest<P: AsRef<Path>>(self, path: P) -> Self {
+        Self {
+            manifest: Some(path.as_ref().to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Allows templates to run their `before` and `after` hook commands.
+    /// Commands are not run by default, so that untrusted templates cannot
+    /// execute anything.
+    ///
+    /// ```rust
+    /// use rrgen::RRgen;
+    ///
+    /// let rgen = RRgen::default().allow_commands(true);
+    ///
+    /// ```
+    #[must_use]
+    pub fn allow_commands(self, allow: bool) -> Self {
+        Self {
+            allow_commands: allow,
+            ..self
+        }
+    }
+
+    /// Runs hook commands with the working directory as cwd, collecting
+    /// their output.
+    fn run_commands(&self, commands: &[String], outputs: &mut Vec<CommandOutput>) -> Result<()> {
+        for command in commands {
+            if self.fs.capturing() {
+                self.printer.skip_command(command, "not writing files");
+            } else if self.allow_commands {
+                self.printer.run_command(command);
+                outputs.push(hooks::run(command, self.working_dir.as_deref())?);
+            } else {
+                self.printer.skip_command(command, "commands not allowed");
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuses to generate when any of the files the template targets has
+    /// uncommitted changes, if required by [`Git::require_clean`], or when a
+    /// [`Git::commit`] could not be signed. Checked before anything is
+    /// written.
+    #[cfg(feature = "git")]
+    fn check_git(&self, frontmatter: &FrontMatter) -> Result<()> {
+        let Some(config) = &self.git else {
+            return Ok(());
+        };
+        let Some(repo) = git::open(self.working_dir.as_deref()) else {
+            return Ok(());
+        };
+        if config.commit.is_some() {
+            git::signature(&repo)?;
+        }
+        if !config.require_clean {
+            return Ok(());
+        }
+        let targets = frontmatter
+            .to
+            .iter()
+            .chain(&frontmatter.delete)
+            .chain(
+                frontmatter
+                    .moves
+                    .iter()
+                    .flat_map(|file| [&file.from, &file.to]),
+            )
+            .chain(
+                frontmatter
+                    .injections
+                    .iter()
+                    .flatten()
+                    .map(|injection| &injection.into),
+            )
+            .map(|path| self.resolve_path(path))
+            .collect::<Vec<_>>();
+        let dirty = git::dirty(&repo, &targets)?;
+        if dirty.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Message(format!(
+                "uncommitted changes in: {}",
+                dirty.join(", ")
+            )))
+        }
+    }
+
+    /// Stages the files generation touched, and commits them when given a
+    /// commit `message`
+    #[cfg(feature = "git")]
+    fn stage(&self, actions: &[Action], message: Option<String>) -> Result<()> {
+        if message.is_none() && !self.git.as_ref().is_some_and(|git| git.stage) {
+            return Ok(());
+        }
+        let Some(repo) = git::open(self.working_dir.as_deref()) else {
+            return Ok(());
+        };
+        let paths = actions
+            .iter()
+            .filter(|action| action.skipped.is_none())
+            .flat_map(|action| [Some(&action.path), action.from.as_ref()])
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>();
+        git::stage(&repo, &paths)?;
+        if let Some(message) = message {
+            git::commit(&repo, &message, &paths)?;
+        }
+        Ok(())
+    }
+
+    fn resolve_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.working_dir.as_ref().map_or_else(
+            || path.as_ref().to_path_buf(),
+            |working_dir| working_dir.join(path.as_ref()),
+        )
+    }
+
+    /// Saves the previous content of every file before it is overwritten,
+    /// deleted, or rewritten by an injection. Each run's backups are listed
+    /// in the manifest's journal; see [`RRgen::prune_backups`] to clean up.
+    ///
+    /// ```rust
+    /// use rrgen::{Backup, RRgen};
+    ///
+    /// let rgen = RRgen::default().with_backups(Backup::Dir(".rrgen/backup".into()));
+    ///
+    /// ```
+    #[must_use]
+    pub fn with_backups(self, backup: Backup) -> Self {
+        Self {
+            backup: Some(backup),
+            ..self
+        }
+    }
+
+    /// Checks and updates the git repository containing the working
+    /// directory, as set in [`Git`].
+    ///
+    /// ```rust
+    /// use rrgen::{Git, RRgen};
+    ///
+    /// let rgen = RRgen::default().with_git(Git {
+    ///     require_clean: true,
+    ///     commit: Some("scaffold {{ name }}".to_string()),
+    ///     ..Git::default()
+    /// });
+    ///
+    /// ```
+    #[cfg(feature = "git")]
+    #[must_use]
+    pub fn with_git(self, git: Git) -> Self {
+        Self {
+            git: Some(git),
+            ..self
+        }
+    }
+
+    /// Reads the manifest kept with [`RRgen::with_manifest`], including the
+    /// journal of past runs. Returns `None` when no manifest is kept.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the manifest cannot be read
+    pub fn read_manifest(&self) -> Result<Option<Manifest>> {
+        self.manifest
+            .as_ref()
+            .map(|path| Manifest::load(&self.fs, &self.resolve_path(path)))
+            .transpose()
+    }
+
+    /// `path` relative to the working directory, as recorded in the manifest
+    fn relative_path(&self, path: &Path) -> String {
+        self.working_dir
+            .as_ref()
+            .and_then(|working_dir| path.strip_prefix(working_dir).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    /// Starts a journal entry for a run, which is filled in as it goes
+    fn start_run(
+        &self,
+        manifest: Option<&Manifest>,
+        kind: RunKind,
+        template: String,
+        vars: &serde_json::Value,
+    ) -> Run {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Run {
+            id: self.run_id(manifest, elapsed),
+            timestamp: elapsed.as_secs(),
+            kind,
+            template,
+            vars: vars.clone(),
+            files: vec![],
+            injections: vec![],
+            backups: BTreeMap::new(),
+        }
+    }
+
+    /// A run id, `<seconds>-<nanoseconds>` since the unix epoch, so ids sort
+    /// by age and stay apart without a manifest to count runs. Runs closer
+    /// than the clock's resolution are kept apart by skipping ids the
+    /// manifest or the backup folder already holds.
+    fn run_id(&self, manifest: Option<&Manifest>, elapsed: Duration) -> String {
+        let backup_dir = match &self.backup {
+            Some(Backup::Dir(dir)) => Some(self.resolve_path(dir)),
+            _ => None,
+        };
+        let mut nanos = elapsed.subsec_nanos();
+        loop {
+            let id = format!("{}-{nanos:09}", elapsed.as_secs());
+            let taken = manifest
+                .is_some_and(|manifest| manifest.runs.iter().any(|run| run.id == id))
+                || backup_dir
+                    .as_ref()
+                    .is_some_and(|dir| self.fs.exists(&dir.join(&id)));
+            if !taken {
+                return id;
+            }
+            nanos += 1;
+        }
+    }
+
+    /// Appends a run to the manifest's journal and saves it
+    fn record_run(
+        &self,
+        manifest: Option<Manifest>,
+        mut run: Run,
+        actions: &[Action],
+    ) -> Result<()> {
+        let (Some(mut manifest), Some(manifest_path)) = (manifest, &self.manifest) else {
+            return Ok(());
+        };
+        if self.fs.capturing() {
+            return Ok(());
+        }
+
+        for action in actions {
+            if action.skipped.is_some()
+                || matches!(action.kind, ActionKind::Injected | ActionKind::Reverted)
+            {
+                continue;
+            }
+            let hash = if action.kind == ActionKind::Deleted {
+                None
+            } else {
+                Some(manifest::hash(&self.fs.read_bytes(&action.path)?))
+            };
+            run.files.push(FileEntry {
+                path: self.relative_path(&action.path),
+                action: action.kind,
+                from: action.from.as_deref().map(|from| self.relative_path(from)),
+                hash,
+            });
+        }
+
+        // the `after` hooks may have moved the injected lines, where destroy
+        // runs record the lines they removed
+        for injection in &mut run.injections {
+            let content = self.fs.read_file(&self.resolve_path(&injection.path))?;
+            if run.kind == RunKind::Generate {
+                injection.line =
+                    locate_block(&content, &injection.content, injection.line).map(|line| line + 1);
+            }
+            injection.hash = Some(manifest::hash(content.as_bytes()));
+        }
+
+        manifest.runs.push(run);
+        manifest.save(&self.fs, &self.resolve_path(manifest_path))
+    }
+
+    /// Saves the current content of `path` as configured with
+    /// [`RRgen::with_backups`], once per run
+    fn backup(&self, run: &mut Run, path: &Path) -> Result<()> {
+        let Some(backup) = self.backup.as_ref().filter(|_| !self.fs.capturing()) else {
+            return Ok(());
+        };
+        let file = self.relative_path(path);
+        if run.backups.contains_key(&file) || !self.fs.exists(path) {
+            return Ok(());
+        }
+        let backup_path = match backup {
+            Backup::Sibling => {
+                let mut backup_path = path.as_os_str().to_owned();
+                backup_path.push(".orig");
+                PathBuf::from(backup_path)
+            }
+            Backup::Dir(dir) => self
+                .resolve_path(dir)
+                .join(&run.id)
+                .join(normal_path(&file)),
+        };
+        let content = self.fs.read_bytes(path)?;
+        self.fs.write_bytes(&backup_path, &content)?;
+        self.printer.backed_up(path, &backup_path);
+        run.backups.insert(
+            file,
+            BackupEntry {
+                path: self.relative_path(&backup_path),
+                hash: manifest::hash(&content),
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes the backup folders of all but the latest `keep` runs, when
+    /// backing up with [`Backup::Dir`]. Returns the removed folders. Anything
+    /// else in the backup folder is left alone, as are [`Backup::Sibling`]
+    /// backups, which never pile up as only the latest is kept per file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the backups cannot be listed or
+    /// removed
+    pub fn prune_backups(&self, keep: usize) -> Result<Vec<PathBuf>> {
+        let Some(Backup::Dir(dir)) = &self.backup else {
+            return Ok(vec![]);
+        };
+        let dir = self.resolve_path(dir);
+        if !self.fs.exists(&dir) {
+            return Ok(vec![]);
+        }
+        // run ids are `<seconds>-<nanoseconds>`, so they sort by age, and
+        // folders not named after a run are not backups
+        let mut runs = self
+            .fs
+            .read_dir(&dir)?
+            .into_iter()
+            .filter_map(|run| {
+                let (seconds, nanos) = run.file_name()?.to_str()?.split_once('-')?;
+                Some((
+                    (seconds.parse::<u64>().ok()?, nanos.parse::<u64>().ok()?),
+                    run,
+                ))
+            })
+            .collect::<Vec<_>>();
+        runs.sort();
+        let pruned = runs
+            .drain(..runs.len().saturating_sub(keep))
+            .map(|(_, run)| run)
+            .collect::<Vec<_>>();
+        for run in &pruned {
+            self.fs.remove_dir(run)?;
+        }
+        Ok(pruned)
+    }
+
+    fn prepare(&self, input: &str, vars: &serde_json::Value) -> Result<Prepared> {
+        let (format, frontmatter, body) = split_template(input)?;
+        let mut vars = variables::apply(format, &frontmatter, vars)?;
+        let mut tera: Tera = self.template_engine.clone();
+
+        // the frontmatter is rendered and parsed first, and its computed `vars`
+        // become part of the context for everything that follows
+        let rendered = tera.render_str(&frontmatter, &Context::from_serialize(&vars)?)?;
+        let frontmatter: FrontMatter = format.parse(&rendered)?;
+        if let serde_json::Value::Object(map) = &mut vars {
+            map.extend(frontmatter.vars.clone());
+        }
+        let context = Context::from_serialize(&vars)?;
+        Ok(Prepared {
+            frontmatter,
+            body,
+            vars,
+            tera,
+            context,
+        })
+    }
+
+    /// The overwrite policy in effect for a template
+    fn policy(&self, frontmatter: &FrontMatter) -> Overwrite {
+        self.overwrite
+            .or(frontmatter.overwrite)
+            .unwrap_or(if frontmatter.skip_exists {
+                Overwrite::Never
+            } else {
+                Overwrite::UnlessModified
+            })
+    }
+
+    /// Why destroy should leave a generated file alone. Only what rrgen
+    /// generated is deleted: the file must match the manifest's record of it,
+    /// or else the newly rendered content, unless deleting is forced.
+    fn destroy_skip_reason(
+        &self,
+        policy: Overwrite,
+        output: &Output,
+        manifest: Option<&Manifest>,
+    ) -> Result<Option<String>> {
+        let existing = self.fs.read_bytes(&output.path)?;
+        let unmodified = match manifest {
+            Some(manifest) if manifest.files.contains_key(&output.file) => {
+                manifest.is_unchanged(&output.file, &existing)
+            }
+            _ => existing == output.content.as_bytes(),
+        };
+        Ok(match policy {
+            Overwrite::Always => None,
+            Overwrite::Never => Some("exists".to_string()),
+            Overwrite::Prompt if !unmodified => {
+                self.overwrite_skip_reason(policy, output, manifest)?
+            }
+            _ if unmodified => None,
+            _ => Some("modified since generated".to_string()),
+        })
+    }
+
+    /// Applies the overwrite `policy` to an existing file, returning the
+    /// reason to skip it if it must not be overwritten.
+    fn overwrite_skip_reason(
+        &self,
+        policy: Overwrite,
+        output: &Output,
+        manifest: Option<&Manifest>,
+    ) -> Result<Option<String>> {
+        Ok(match policy {
+            Overwrite::Always => None,
+            Overwrite::Never => Some("exists".to_string()),
+            Overwrite::IfUnchanged => {
+                let existing = self.fs.read_bytes(&output.path)?;
+                match manifest {
+                    Some(manifest) if manifest.is_unchanged(&output.file, &existing) => None,
+                    Some(manifest) if manifest.files.contains_key(&output.file) => {
+                        Some("modified since generated".to_string())
+                    }
+                    _ => Some("no record of generating it".to_string()),
+                }
+            }
+            Overwrite::Merge if matches!(output.content, Content::Text(_)) => {
+                let existing = self.fs.read_bytes(&output.path)?;
+                match manifest {
+                    Some(manifest) if manifest.is_unchanged(&output.file, &existing) => None,
+                    Some(manifest) if manifest.files.contains_key(&output.file) => {
+                        if self.read_base(&output.file)?.is_some() {
+                            None
+                        } else {
+                            Some("no base to merge against".to_string())
+                        }
+                    }
+                    _ => Some("no record of generating it".to_string()),
+                }
+            }
+            Overwrite::UnlessModified | Overwrite::Merge => match manifest {
+                Some(manifest)
+                    if manifest.files.contains_key(&output.file)
+                        && !manifest
+                            .is_unchanged(&output.file, &self.fs.read_bytes(&output.path)?) =>
+                {
+                    Some("modified since generated".to_string())
+                }
+                _ => None,
+            },
+            Overwrite::Prompt => {
+                let existing = self.fs.read_bytes(&output.path)?;
+                let content = output.content.as_bytes();
+                if existing == content
+                    || self.resolve_conflict(
+                        &output.path,
+                        &String::from_utf8_lossy(&existing),
+                        &String::from_utf8_lossy(content),
+                    )? == Resolution::Overwrite
+                {
+                    None
+                } else {
+                    Some("declined".to_string())
+                }
+            }
+        })
+    }
+
+    /// Asks the [`ConflictResolver`] until it answers whether to overwrite
+    /// or skip, showing a diff whenever it asks for one.
+    fn resolve_conflict(
+        &self,
+        file_to: &Path,
+        existing: &str,
+        generated: &str,
+    ) -> Result<Resolution> {
+        loop {
+            let resolution = self
+                .resolve_all
+                .get()
+                .unwrap_or_else(|| self.conflict_resolver.resolve(file_to, existing, generated));
+            match resolution {
+                Resolution::Overwrite | Resolution::Skip => return Ok(resolution),
+                Resolution::OverwriteAll | Resolution::SkipAll => {
+                    self.resolve_all.set(Some(resolution));
+                    return Ok(if resolution == Resolution::OverwriteAll {
+                        Resolution::Overwrite
+                    } else {
+                        Resolution::Skip
+                    });
+                }
+                Resolution::Diff => {
+                    let diff = similar::TextDiff::from_lines(existing, generated)
+                        .unified_diff()
+                        .header("current", "generated")
+                        .to_string();
+                    self.printer.diff(file_to, &diff);
+                }
+                Resolution::Abort => return Err(Error::Aborted),
+            }
+        }
+    }
+
+    /// Deletes and moves files as listed in the frontmatter. A deletion is
+    /// treated as overwriting the file with nothing, and a move as
+    /// overwriting its destination, so both follow the overwrite `policy`.
+    fn apply_file_operations(
+        &self,
+        frontmatter: &FrontMatter,
+        policy: Overwrite,
+        mut manifest: Option<&mut Manifest>,
+        run: &mut Run,
+    ) -> Result<Vec<Action>> {
+        let mut actions = Vec::new();
+        for file in &frontmatter.moves {
+            let from = self.resolve_path(&file.from);
+            let output = Output {
+                path: self.resolve_path(&file.to),
+                file: file.to.clone(),
+                content: Content::Bytes(if self.fs.exists(&from) {
+                    self.fs.read_bytes(&from)?
+                } else {
+                    vec![]
+                }),
+            };
+            let skipped = if !self.fs.exists(&from) {
+                Some("does not exist".to_string())
+            } else if self.fs.exists(&output.path) {
+                self.overwrite_skip_reason(policy, &output, manifest.as_deref())?
+            } else {
+                None
+            };
+            let mut action = if let Some(reason) = skipped {
+                self.printer.skipped(&from, &reason);
+                Action::skipped(&output.path, ActionKind::Moved, &reason)
+            } else {
+                self.backup(run, &output.path)?;
+                self.fs.rename_file(&from, &output.path)?;
+                self.printer.moved(&from, &output.path);
+                if let Some(manifest) = manifest.as_deref_mut() {
+                    manifest.rename(&file.from, &file.to);
+                }
+                if let (Some(base_from), Some(base_to)) =
+                    (self.snapshot_path(&file.from), self.snapshot_path(&file.to))
+                {
+                    if self.fs.exists(&base_from) {
+                        self.fs.rename_file(&base_from, &base_to)?;
+                    }
+                }
+                Action::done(&output.path, ActionKind::Moved, output.content.as_bytes())
+            };
+            action.from = Some(from);
+            actions.push(action);
+        }
+
+        for file in &frontmatter.delete {
+            let output = Output {
+                path: self.resolve_path(file),
+                file: file.clone(),
+                content: Content::Bytes(vec![]),
+            };
+            actions.push(self.delete_output(policy, &output, manifest.as_deref_mut(), run)?);
+        }
+        Ok(actions)
+    }
+
+    /// Deletes a file unless the overwrite `policy` says otherwise.
+    fn delete_output(
+        &self,
+        policy: Overwrite,
+        output: &Output,
+        manifest: Option<&mut Manifest>,
+        run: &mut Run,
+    ) -> Result<Action> {
+        let skipped = if self.fs.exists(&output.path) {
+            if run.kind == RunKind::Destroy {
+                self.destroy_skip_reason(policy, output, manifest.as_deref())?
+            } else {
+                self.overwrite_skip_reason(policy, output, manifest.as_deref())?
+            }
+        } else {
+            Some("does not exist".to_string())
+        };
+        if let Some(reason) = skipped {
+            self.printer.skipped(&output.path, &reason);
+            return Ok(Action::skipped(&output.path, ActionKind::Deleted, &reason));
+        }
+        self.backup(run, &output.path)?;
+        self.fs.remove_file(&output.path)?;
+        self.printer.removed(&output.path);
+        if let Some(manifest) = manifest {
+            manifest.files.remove(&output.file);
+        }
+        if let Some(base) = self.snapshot_path(&output.file) {
+            if self.fs.exists(&base) {
+                self.fs.remove_file(&base)?;
+            }
+        }
+        Ok(Action::done(&output.path, ActionKind::Deleted, &[]))
+    }
+
+    /// Writes a generated file unless the overwrite `policy` says otherwise.
+    fn write_output(
+        &self,
+        policy: Overwrite,
+        output: &Output,
+        mode: Option<u32>,
+        manifest: Option<&mut Manifest>,
+        run: &mut Run,
+    ) -> Result<Action> {
+        let kind = if self.fs.exists(&output.path) {
+            if policy == Overwrite::Never {
+                self.printer.skip_exists(&output.path);
+                return Ok(Action::skipped(
+                    &output.path,
+                    ActionKind::Overwritten,
+                    "exists",
+                ));
+            }
+            if let Some(reason) = self.overwrite_skip_reason(policy, output, manifest.as_deref())? {
+                self.printer.skipped(&output.path, &reason);
+                return Ok(Action::skipped(
+                    &output.path,
+                    ActionKind::Overwritten,
+                    &reason,
+                ));
+            }
+            self.backup(run, &output.path)?;
+            let merged = if policy == Overwrite::Merge {
+                self.merge_output(output, manifest.as_deref())?
+            } else {
+                None
+            };
+            if let Some(merged) = merged {
+                self.printer.merged(&output.path, merged.conflicts);
+                self.fs.write_file(&output.path, &merged.content)?;
+                self.record_output(output, manifest)?;
+                let kind = if merged.conflicts == 0 {
+                    ActionKind::Merged
+                } else {
+                    ActionKind::Conflicted
+                };
+                return Ok(Action::done(&output.path, kind, merged.content.as_bytes()));
+            }
+            self.printer.overwrite_file(&output.path);
+            ActionKind::Overwritten
         } else {
-            PathBuf::from(&frontmatter.to)
+            self.printer.add_file(&output.path);
+            ActionKind::Created
+        };
+
+        match &output.content {
+            Content::Text(text) => self.fs.write_file(&output.path, text)?,
+            Content::Bytes(bytes) => self.fs.write_bytes(&output.path, bytes)?,
+        }
+        if let Some(mode) = mode {
+            self.fs.set_mode(&output.path, mode)?;
+        }
+        self.record_output(output, manifest)?;
+        Ok(Action::done(&output.path, kind, output.content.as_bytes()))
+    }
+
+    /// Three-way merges the generated content into a file modified since it
+    /// was last generated. Returns `None` when the file is unmodified and can
+    /// simply be overwritten.
+    fn merge_output(
+        &self,
+        output: &Output,
+        manifest: Option<&Manifest>,
+    ) -> Result<Option<merge::Merged>> {
+        let existing = self.fs.read_file(&output.path)?;
+        if manifest.is_some_and(|manifest| manifest.is_unchanged(&output.file, existing.as_bytes()))
+        {
+            return Ok(None);
+        }
+        let (Content::Text(generated), Some(base)) =
+            (&output.content, self.read_base(&output.file)?)
+        else {
+            return Ok(None);
+        };
+        Ok(Some(merge::merge(&base, &existing, generated)))
+    }
+
+    /// Records generated content in the manifest, keeping the text as the
+    /// base of future merges.
+    fn record_output(&self, output: &Output, manifest: Option<&mut Manifest>) -> Result<()> {
+        let Some(manifest) = manifest else {
+            return Ok(());
         };
+        manifest.record(&output.file, output.content.as_bytes());
+        if let (Content::Text(text), Some(base)) =
+            (&output.content, self.snapshot_path(&output.file))
+        {
+            self.fs.write_file(&base, text)?;
+        }
+        Ok(())
+    }
+
+    /// Where the last generated content of `file` is kept, next to the
+    /// manifest
+    fn base_path(&self, file: &str) -> Option<PathBuf> {
+        let manifest = self.resolve_path(self.manifest.as_ref()?);
+        Some(manifest.parent()?.join("base").join(normal_path(file)))
+    }
+
+    /// Like [`RRgen::base_path`], for changing the kept content, which is
+    /// left alone when generating a patch
+    fn snapshot_path(&self, file: &str) -> Option<PathBuf> {
+        self.base_path(file).filter(|_| !self.fs.capturing())
+    }
+
+    fn read_base(&self, file: &str) -> Result<Option<String>> {
+        match self.base_path(file) {
+            Some(base) if self.fs.exists(&base) => Ok(Some(self.fs.read_file(&base)?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Generate from a template contained in `input`
+    ///
+    /// Template-relative paths, such as an injection's `content_from`, are
+    /// resolved against the current directory. Use [`RRgen::generate_file`] to
+    /// resolve them against the template's own location.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if operation fails
+    pub fn generate(&self, input: &str, vars: &serde_json::Value) -> Result<GenResult> {
+        self.generate_template(input, None, vars)
+    }
+
+    /// Generate from the template file at `path`
+    ///
+    /// Template-relative paths, such as an injection's `content_from`, are
+    /// resolved against the folder containing the template.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the template cannot be read or
+    /// the operation fails
+    pub fn generate_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        vars: &serde_json::Value,
+    ) -> Result<GenResult> {
+        let path = path.as_ref();
+        let input = fs_err::read_to_string(path)?;
+        self.generate_template(&input, Some(path), vars)
+    }
+
+    /// Generate from a template contained in `input` without writing
+    /// anything, returning a unified patch of every file it would create,
+    /// overwrite, delete, move or inject into instead. Paths in the patch are
+    /// relative to the working directory, with `a/` and `b/` prefixes, so
+    /// that `git apply` or `patch -p1` can apply it later. Hook commands are
+    /// not run and the manifest is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the operation fails, or a file
+    /// that would change is not UTF-8
+    pub fn generate_patch(&self, input: &str, vars: &serde_json::Value) -> Result<String> {
+        self.patch_template(input, None, vars)
+    }
+
+    /// Generate a patch from the template file at `path`, see
+    /// [`RRgen::generate_patch`]. Template-relative paths are resolved as
+    /// with [`RRgen::generate_file`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the template cannot be read, the
+    /// operation fails, or a file that would change is not UTF-8
+    pub fn generate_patch_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        vars: &serde_json::Value,
+    ) -> Result<String> {
+        let path = path.as_ref();
+        let input = fs_err::read_to_string(path)?;
+        self.patch_template(&input, Some(path), vars)
+    }
+
+    fn patch_template(
+        &self,
+        input: &str,
+        template_file: Option<&Path>,
+        vars: &serde_json::Value,
+    ) -> Result<String> {
+        self.fs.capture();
+        let result = self.generate_template(input, template_file, vars);
+        let changes = self.fs.finish();
+        result?;
+        self.fs.patch(&changes, |path| self.relative_path(path))
+    }
 
-        if frontmatter.skip_exists && self.fs.exists(&path_to) {
-            self.printer.skip_exists(&path_to);
-            return Ok(GenResult::Skipped);
+    /// Runs `generate` without writing anything, and collects every file it
+    /// creates or changes into an archive instead, with paths relative to the
+    /// working directory. Injections apply to the files on disk, and later
+    /// templates see what earlier ones generated. Hook commands are not run
+    /// and the manifest is left untouched.
+    ///
+    /// ```rust,no_run
+    /// use rrgen::{ArchiveFormat, RRgen};
+    /// use serde_json::json;
+    ///
+    /// let rgen = RRgen::with_working_dir("starters/base");
+    /// let archive = rgen.archive(ArchiveFormat::TarGz, |rgen| {
+    ///     rgen.generate_file("templates/app.t", &json!({"name": "shop"}))?;
+    ///     rgen.generate_file("templates/model.t", &json!({"name": "product"}))?;
+    ///     Ok(())
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `generate` fails, or the archive
+    /// cannot be built
+    #[cfg(feature = "archive")]
+    pub fn archive<F>(&self, format: ArchiveFormat, generate: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&Self) -> Result<()>,
+    {
+        self.fs.capture();
+        let result = generate(self);
+        let changes = self.fs.finish();
+        result?;
+        let mut entries = Vec::new();
+        for (path, change) in &changes {
+            let overlay::Change::Written { content, mode } = change else {
+                continue;
+            };
+            // files that already exist keep their mode unless the template
+            // sets one
+            let mode = match mode {
+                Some(mode) => *mode,
+                None => self.fs.mode(path)?.unwrap_or(0o644),
+            };
+            entries.push((self.archive_path(path)?, content.as_slice(), mode));
         }
-        if let Some(skip_glob) = frontmatter.skip_glob {
-            if glob::glob(&skip_glob)?.count() > 0 {
-                self.printer.skip_exists(&path_to);
-                return Ok(GenResult::Skipped);
+        archive::build(format, entries)
+    }
+
+    /// The path of `path` inside an archive: relative to the working dir,
+    /// which it must not leave
+    #[cfg(feature = "archive")]
+    fn archive_path(&self, path: &Path) -> Result<String> {
+        let relative = self.relative_path(path);
+        let mut parts = Vec::new();
+        for component in Path::new(&relative).components() {
+            match component {
+                std::path::Component::Normal(part) => parts.push(part.to_string_lossy()),
+                std::path::Component::CurDir => {}
+                _ => {
+                    return Err(Error::Message(format!(
+                        "cannot archive {}: outside the working dir",
+                        path.display()
+                    )))
+                }
             }
         }
+        Ok(parts.join("/"))
+    }
+
+    /// Reverse a template contained in `input`: delete the files it generates
+    /// and remove exactly the content each of its injections added, given the
+    /// same vars it was generated with.
+    ///
+    /// Deleting follows the overwrite policy. Injected content that is no
+    /// longer found as-is is left alone, as are `remove_lines` and custom
+    /// strategy injections, `delete` and `move`, which cannot be reversed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if operation fails
+    pub fn destroy(&self, input: &str, vars: &serde_json::Value) -> Result<GenResult> {
+        self.destroy_template(input, None, vars)
+    }
 
-        if self.fs.exists(&path_to) {
-            self.printer.overwrite_file(&path_to);
+    /// Reverse the template file at `path`, see [`RRgen::destroy`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the template cannot be read or
+    /// the operation fails
+    pub fn destroy_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        vars: &serde_json::Value,
+    ) -> Result<GenResult> {
+        let path = path.as_ref();
+        let input = fs_err::read_to_string(path)?;
+        self.destroy_template(&input, Some(path), vars)
+    }
+
+    /// Undo the last run recorded in the manifest's journal: files it created
+    /// are deleted, files it overwrote, deleted or injected into are restored
+    /// from their backups, and moved files are moved back. Injections into
+    /// files without a backup are removed line by line.
+    ///
+    /// Nothing is changed when any of the files was modified since the run,
+    /// when an injection cannot be found, or when a backup needed to restore
+    /// a file is missing or was replaced since.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no manifest is kept, the journal
+    /// is empty, the run cannot be undone, or the operation fails
+    pub fn undo_last(&self) -> Result<GenResult> {
+        let Some(mut manifest) = self.read_manifest()? else {
+            return Err(Error::Message(
+                "cannot undo: no manifest is kept".to_string(),
+            ));
+        };
+        let Some(run) = manifest.runs.pop() else {
+            return Err(Error::Message("cannot undo: no runs recorded".to_string()));
+        };
+
+        let mut modified = BTreeSet::new();
+        let hashes = run.files.iter().map(|file| (&file.path, &file.hash)).chain(
+            run.injections
+                .iter()
+                .map(|injection| (&injection.path, &injection.hash)),
+        );
+        for (file, hash) in hashes {
+            let path = self.resolve_path(file);
+            let current = if self.fs.exists(&path) {
+                Some(manifest::hash(&self.fs.read_bytes(&path)?))
+            } else {
+                None
+            };
+            if current != *hash {
+                modified.insert(file.as_str());
+            }
+        }
+        if !modified.is_empty() {
+            return Err(Error::Message(format!(
+                "cannot undo run {}: modified since: {}",
+                run.id,
+                modified.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+
+        let mut missing = run
+            .files
+            .iter()
+            .filter(|file| {
+                matches!(
+                    file.action,
+                    ActionKind::Overwritten
+                        | ActionKind::Merged
+                        | ActionKind::Conflicted
+                        | ActionKind::Deleted
+                )
+            })
+            .map(|file| &file.path)
+            .chain(
+                run.injections
+                    .iter()
+                    .filter(|_| run.kind == RunKind::Destroy)
+                    .map(|injection| &injection.path),
+            )
+            .filter(|file| !run.backups.contains_key(*file))
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+
+        // every backup is read and checked before anything is restored
+        let mut backups = BTreeMap::new();
+        let mut replaced = BTreeSet::new();
+        for (file, backup) in &run.backups {
+            let path = self.resolve_path(&backup.path);
+            if !self.fs.exists(&path) {
+                missing.insert(file.as_str());
+                continue;
+            }
+            let content = self.fs.read_bytes(&path)?;
+            if manifest::hash(&content) == backup.hash {
+                backups.insert(file.as_str(), content);
+            } else {
+                replaced.insert(file.as_str());
+            }
+        }
+        if !missing.is_empty() {
+            return Err(Error::Message(format!(
+                "cannot undo run {}: no backup of: {}",
+                run.id,
+                missing.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+        if !replaced.is_empty() {
+            return Err(Error::Message(format!(
+                "cannot undo run {}: backup replaced since: {}",
+                run.id,
+                replaced.into_iter().collect::<Vec<_>>().join(", ")
+            )));
+        }
+
+        // injections without a backup are reverted in memory first, so that
+        // nothing is written unless all of them can be
+        let mut reverted = BTreeMap::new();
+        for injection in run.injections.iter().rev() {
+            if run.backups.contains_key(&injection.path) {
+                continue;
+            }
+            let file_content = match reverted.remove(injection.path.as_str()) {
+                Some(content) => content,
+                None => self.fs.read_file(&self.resolve_path(&injection.path))?,
+            };
+            let Some(pos) = locate_block(&file_content, &injection.content, injection.line) else {
+                return Err(Error::Message(format!(
+                    "cannot undo injection into {}: content not found",
+                    injection.path
+                )));
+            };
+            let count = injection.content.lines().count();
+            reverted.insert(
+                injection.path.as_str(),
+                remove_lines(&file_content, pos, count),
+            );
+        }
+
+        // undo in the reverse order of the run: injections came last
+        let mut actions = Vec::new();
+        let mut restored = BTreeSet::new();
+        for injection in run.injections.iter().rev() {
+            let path = self.resolve_path(&injection.path);
+            if run.backups.contains_key(&injection.path) {
+                if restored.insert(&injection.path) {
+                    actions.push(self.restore(&injection.path, &backups[injection.path.as_str()])?);
+                }
+                continue;
+            }
+            if let Some(content) = reverted.remove(injection.path.as_str()) {
+                self.fs.write_file(&path, &content)?;
+            }
+            self.printer.reverted(&path, &injection.content);
+            actions.push(Action::done(
+                &path,
+                ActionKind::Reverted,
+                injection.content.as_bytes(),
+            ));
+        }
+
+        for file in run.files.iter().rev() {
+            let path = self.resolve_path(&file.path);
+            match file.action {
+                ActionKind::Created => {
+                    self.fs.remove_file(&path)?;
+                    self.printer.removed(&path);
+                    actions.push(Action::done(&path, ActionKind::Deleted, &[]));
+                }
+                ActionKind::Moved => {
+                    let from = self.resolve_path(file.from.as_deref().unwrap_or_default());
+                    self.fs.rename_file(&path, &from)?;
+                    self.printer.moved(&path, &from);
+                    let mut action = Action::done(&from, ActionKind::Moved, &[]);
+                    action.from = Some(path);
+                    actions.push(action);
+                    if run.backups.contains_key(&file.path) {
+                        actions.push(self.restore(&file.path, &backups[file.path.as_str()])?);
+                    }
+                }
+                _ => {
+                    if restored.insert(&file.path) {
+                        actions.push(self.restore(&file.path, &backups[file.path.as_str()])?);
+                    }
+                }
+            }
+            // rrgen no longer knows what it last generated into these files
+            manifest.files.remove(&file.path);
+            if let Some(base) = self.snapshot_path(&file.path) {
+                if self.fs.exists(&base) {
+                    self.fs.remove_file(&base)?;
+                }
+            }
+        }
+
+        if let Some(manifest_path) = &self.manifest {
+            manifest.save(&self.fs, &self.resolve_path(manifest_path))?;
+        }
+        Ok(GenResult::Generated {
+            message: Some(format!("undid run {}", run.id)),
+            actions,
+            commands: vec![],
+        })
+    }
+
+    /// Restores `file` to its backed up `content`
+    fn restore(&self, file: &str, content: &[u8]) -> Result<Action> {
+        let path = self.resolve_path(file);
+        self.fs.write_bytes(&path, content)?;
+        self.printer.restored(&path);
+        Ok(Action::done(&path, ActionKind::Restored, content))
+    }
+
+    fn destroy_template(
+        &self,
+        input: &str,
+        template_file: Option<&Path>,
+        vars: &serde_json::Value,
+    ) -> Result<GenResult> {
+        let template_dir = template_file.and_then(Path::parent);
+        let Prepared {
+            frontmatter,
+            body,
+            vars,
+            mut tera,
+            context,
+        } = self.prepare(input, vars)?;
+
+        if let Some(reason) = condition_skip_reason(
+            &mut tera,
+            &context,
+            frontmatter.when.as_deref(),
+            frontmatter.unless.as_deref(),
+        )? {
+            let actions = frontmatter
+                .to
+                .iter()
+                .map(|to| Action::skipped(&self.resolve_path(to), ActionKind::Deleted, &reason))
+                .collect();
+            return Ok(GenResult::Skipped {
+                actions,
+                commands: vec![],
+            });
+        }
+
+        let policy = self.policy(&frontmatter);
+        let mut manifest = self.read_manifest()?;
+        let mut run = self.start_run(
+            manifest.as_ref(),
+            RunKind::Destroy,
+            template_id(input, template_file),
+            &vars,
+        );
+
+        let mut actions = Vec::new();
+        let path_to = frontmatter.to.as_ref().map(|to| self.resolve_path(to));
+        for output in &outputs(&frontmatter, path_to, template_dir, || {
+            tera.render_str(&body, &context)
+        })? {
+            actions.push(self.delete_output(policy, output, manifest.as_mut(), &mut run)?);
+        }
+
+        for injection in frontmatter.injections.iter().flatten() {
+            let injection_to = self.resolve_path(&injection.into);
+            let skipped = if let Some(reason) = condition_skip_reason(
+                &mut tera,
+                &context,
+                injection.when.as_deref(),
+                injection.unless.as_deref(),
+            )? {
+                Some(reason)
+            } else if injection.strategy.is_some() || injection.remove_lines.is_some() {
+                Some("cannot be reversed".to_string())
+            } else if !self.fs.exists(&injection_to) {
+                Some("does not exist".to_string())
+            } else {
+                let content = injection_content(&mut tera, &context, template_dir, injection)?;
+                let file_content = self.fs.read_file(&injection_to)?;
+                let line = find_block(&file_content, &content, injection.towards_end());
+                if let Some(new_content) =
+                    remove_block(&file_content, &content, injection.towards_end())
+                {
+                    self.backup(&mut run, &injection_to)?;
+                    self.fs.write_file(&injection_to, &new_content)?;
+                    run.injections.push(InjectionEntry {
+                        path: self.relative_path(&injection_to),
+                        line: line.map(|line| line + 1),
+                        content: content.clone(),
+                        hash: None,
+                    });
+                    self.printer.reverted(&injection_to, &content);
+                    actions.push(Action::done(
+                        &injection_to,
+                        ActionKind::Reverted,
+                        content.as_bytes(),
+                    ));
+                    None
+                } else {
+                    Some("content not found".to_string())
+                }
+            };
+            if let Some(reason) = skipped {
+                self.printer.skipped(&injection_to, &reason);
+                actions.push(Action::skipped(
+                    &injection_to,
+                    ActionKind::Reverted,
+                    &reason,
+                ));
+            }
+        }
+
+        self.record_run(manifest, run, &actions)?;
+        Ok(GenResult::Generated {
+            message: None,
+            actions,
+            commands: vec![],
+        })
+    }
+
+    fn generate_template(
+        &self,
+        input: &str,
+        template_file: Option<&Path>,
+        vars: &serde_json::Value,
+    ) -> Result<GenResult> {
+        let template_dir = template_file.and_then(Path::parent);
+        let Prepared {
+            frontmatter,
+            body,
+            vars,
+            mut tera,
+            context,
+        } = self.prepare(input, vars)?;
+        let vars = &vars;
+
+        let path_to = frontmatter.to.as_ref().map(|to| self.resolve_path(to));
+        // what to report when the whole template is skipped: the generated
+        // file, or the injection targets of an injection-only template
+        let skipped_paths = path_to.as_ref().map_or_else(
+            || {
+                frontmatter
+                    .injections
+                    .iter()
+                    .flatten()
+                    .map(|injection| self.resolve_path(&injection.into))
+                    .collect()
+            },
+            |path_to| vec![path_to.clone()],
+        );
+        let skipped_kind = if path_to.is_some() {
+            ActionKind::Created
         } else {
-            self.printer.add_file(&path_to);
+            ActionKind::Injected
+        };
+        let skipped_actions = |reason: &str| {
+            skipped_paths
+                .iter()
+                .map(|path| Action::skipped(path, skipped_kind, reason))
+                .collect::<Vec<_>>()
+        };
+
+        if let Some(reason) = condition_skip_reason(
+            &mut tera,
+            &context,
+            frontmatter.when.as_deref(),
+            frontmatter.unless.as_deref(),
+        )? {
+            for path in &skipped_paths {
+                self.printer.skipped(path, &reason);
+            }
+            return Ok(GenResult::Skipped {
+                actions: skipped_actions(&reason),
+                commands: vec![],
+            });
+        }
+
+        let policy = self.policy(&frontmatter);
+        if let Some(skip_glob) = &frontmatter.skip_glob {
+            if glob::glob(skip_glob)?.count() > 0 {
+                for path in &skipped_paths {
+                    self.printer.skip_exists(path);
+                }
+                return Ok(GenResult::Skipped {
+                    actions: skipped_actions("skip_glob matched"),
+                    commands: vec![],
+                });
+            }
+        }
+
+        #[cfg(feature = "git")]
+        if !self.fs.capturing() {
+            self.check_git(&frontmatter)?;
+        }
+
+        let mut commands = Vec::new();
+        self.run_commands(&frontmatter.before, &mut commands)?;
+
+        let mut manifest = self.read_manifest()?;
+        let mut run = self.start_run(
+            manifest.as_ref(),
+            RunKind::Generate,
+            template_id(input, template_file),
+            vars,
+        );
+
+        let mut actions =
+            self.apply_file_operations(&frontmatter, policy, manifest.as_mut(), &mut run)?;
+
+        let outputs = outputs(&frontmatter, path_to, template_dir, || {
+            tera.render_str(&body, &context)
+        })?;
+        let mut written = Vec::new();
+        for output in &outputs {
+            written.push(self.write_output(
+                policy,
+                output,
+                frontmatter.mode,
+                manifest.as_mut(),
+                &mut run,
+            )?);
+        }
+        // only skipping the generated files skips the whole template, as
+        // `delete` and `move` may simply have nothing left to do
+        let skipped = !written.is_empty() && written.iter().all(|action| action.skipped.is_some());
+        actions.extend(written);
+        if skipped {
+            return Ok(GenResult::Skipped { actions, commands });
         }
-        // write main file
-        self.fs.write_file(&path_to, &body)?;
 
         // handle injects
         if let Some(injections) = frontmatter.injections {
             for injection in &injections {
-                let injection_to = self.working_dir.as_ref().map_or_else(
-                    || PathBuf::from(&injection.into),
-                    |working_dir| working_dir.join(&injection.into),
-                );
+                let injection_to = self.resolve_path(&injection.into);
+                if let Some(reason) = condition_skip_reason(
+                    &mut tera,
+                    &context,
+                    injection.when.as_deref(),
+                    injection.unless.as_deref(),
+                )? {
+                    self.printer.skipped(&injection_to, &reason);
+                    actions.push(Action::skipped(
+                        &injection_to,
+                        ActionKind::Injected,
+                        &reason,
+                    ));
+                    continue;
+                }
                 if !self.fs.exists(&injection_to) {
                     return Err(Error::Message(format!(
                         "cannot inject into {}: file does not exist",
@@ -262,15 +2446,25 @@ impl RRgen {
                 }
 
                 let file_content = self.fs.read_file(&injection_to)?;
-                let content = &injection.content;
+                let content = &injection_content(&mut tera, &context, template_dir, injection)?;
 
                 if let Some(skip_if) = &injection.skip_if {
                     if skip_if.is_match(&file_content) {
+                        actions.push(Action::skipped(
+                            &injection_to,
+                            ActionKind::Injected,
+                            "skip_if matched",
+                        ));
                         continue;
                     }
                 }
 
-                let new_content = if injection.prepend {
+                let new_content = if let Some(name) = &injection.strategy {
+                    let strategy = self.injection_strategies.get(name).ok_or_else(|| {
+                        Error::Message(format!("unknown injection strategy: {name}"))
+                    })?;
+                    strategy.inject(&file_content, content, &injection.config, vars)?
+                } else if injection.prepend {
                     format!("{content}\n{file_content}")
                 } else if injection.append {
                     format!("{file_content}\n{content}")
@@ -309,16 +2503,107 @@ impl RRgen {
                         .collect::<Vec<_>>();
                     lines.join("\n")
                 } else {
-                    println!("warning: no injection made");
                     file_content.clone()
                 };
+                if new_content.lines().eq(file_content.lines()) {
+                    actions.push(Action::skipped(
+                        &injection_to,
+                        ActionKind::Injected,
+                        "no injection made",
+                    ));
+                    continue;
+                }
 
+                self.backup(&mut run, &injection_to)?;
                 self.fs.write_file(&injection_to, &new_content)?;
+                run.injections.push(InjectionEntry {
+                    path: self.relative_path(&injection_to),
+                    line: find_block(&new_content, content, injection.towards_end())
+                        .map(|line| line + 1),
+                    content: content.clone(),
+                    hash: None,
+                });
                 self.printer.injected(&injection_to);
+                actions.push(Action::done(
+                    &injection_to,
+                    ActionKind::Injected,
+                    content.as_bytes(),
+                ));
             }
         }
+        // the run is recorded once the `after` hooks are done, as formatters
+        // rewrite what was generated, but also when one of them fails
+        let after = self.run_commands(&frontmatter.after, &mut commands);
+        self.record_run(manifest, run, &actions)?;
+        after?;
+
+        #[cfg(feature = "git")]
+        if !self.fs.capturing() {
+            let message = self
+                .git
+                .as_ref()
+                .and_then(|git| git.commit.as_deref())
+                .map(|message| tera.render_str(message, &context))
+                .transpose()?;
+            self.stage(&actions, message)?;
+        }
         Ok(GenResult::Generated {
             message: frontmatter.message.clone(),
+            actions,
+            commands,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml_mode(mode: &str) -> Result<Option<u32>> {
+        Ok(serde_yaml::from_str::<FrontMatter>(&format!("mode: {mode}"))?.mode)
+    }
+
+    fn toml_mode(mode: &str) -> Result<Option<u32>> {
+        Ok(toml::from_str::<FrontMatter>(&format!("mode = {mode}"))?.mode)
+    }
+
+    #[test]
+    fn mode_reads_octal_literals() {
+        assert_eq!(yaml_mode("\"0755\"").unwrap(), Some(0o755));
+        assert_eq!(yaml_mode("0755").unwrap(), Some(0o755));
+        assert_eq!(yaml_mode("755").unwrap(), Some(0o755));
+        assert_eq!(yaml_mode("0o755").unwrap(), Some(0o755));
+        assert_eq!(yaml_mode("0o644").unwrap(), Some(0o644));
+        assert_eq!(toml_mode("0o755").unwrap(), Some(0o755));
+        assert_eq!(toml_mode("0o750").unwrap(), Some(0o750));
+        assert_eq!(toml_mode("\"0o644\"").unwrap(), Some(0o644));
+        assert_eq!(toml_mode("\"0755\"").unwrap(), Some(0o755));
+        assert_eq!(yaml_mode("~").unwrap(), None);
+        assert_eq!(
+            serde_json::from_str::<FrontMatter>(r#"{"mode": "0755"}"#)
+                .unwrap()
+                .mode,
+            Some(0o755)
+        );
+    }
+
+    #[test]
+    fn mode_rejects_invalid_digits_and_ambiguous_numbers() {
+        assert!(yaml_mode("0789").is_err());
+        assert!(yaml_mode("rwx").is_err());
+        assert!(yaml_mode("0o17777").is_err());
+        // a decimal 755 in TOML or JSON can only be octal digits written as
+        // a number
+        assert!(toml_mode("755").is_err());
+        assert!(toml_mode("444").is_err());
+        assert!(toml_mode("500").is_err());
+        // as is TOML's 0o644, which arrives as 420
+        assert!(toml_mode("0o4755").unwrap_err().to_string().ends_with(
+            "ambiguous file mode: 2541; write it as a string, \"4755\" for 0o4755 or \"2541\" \
+             for 0o2541\n"
+        ));
+        assert!(toml_mode("0o644").is_err());
+        assert!(toml_mode("0o17777").is_err());
+        assert!(serde_json::from_str::<FrontMatter>(r#"{"mode": 755}"#).is_err());
+    }
+}
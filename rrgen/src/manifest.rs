@@ -0,0 +1,140 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{ActionKind, FsDriver, Result};
+
+/// Hashes generated content, as recorded in the manifest
+#[must_use]
+pub fn hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// The last generated content of a file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileRecord {
+    pub hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunKind {
+    Generate,
+    Destroy,
+}
+
+/// A file touched by a run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileEntry {
+    pub path: String,
+    pub action: ActionKind,
+    /// the original path of a moved file
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// hash of the file's content after the run, unless it was deleted
+    pub hash: Option<String>,
+}
+
+/// A snippet added or removed by an injection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InjectionEntry {
+    pub path: String,
+    /// the line the snippet starts at, counting from 1
+    pub line: Option<usize>,
+    pub content: String,
+    /// hash of the file's content after the run
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// Where the previous content of a file was backed up
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BackupEntry {
+    pub path: String,
+    /// hash of the backed up content, so that a backup replaced since, such
+    /// as a sibling `.orig` file, is not restored in its place
+    pub hash: String,
+}
+
+/// A single `generate` or `destroy` of a template
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Run {
+    pub id: String,
+    /// seconds since the unix epoch
+    pub timestamp: u64,
+    pub kind: RunKind,
+    /// the template's path, or a hash of its content when generated from a
+    /// string
+    pub template: String,
+    pub vars: serde_json::Value,
+    pub files: Vec<FileEntry>,
+    pub injections: Vec<InjectionEntry>,
+    /// where the previous content of each file was backed up, keyed by the
+    /// file's path
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub backups: BTreeMap<String, BackupEntry>,
+}
+
+/// A project-local record of what rrgen generated: the last generated
+/// content of each file, keyed by the path given in the template, and a
+/// journal of every run. Paths are relative to the working directory.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Manifest {
+    #[serde(default)]
+    pub files: BTreeMap<String, FileRecord>,
+
+    #[serde(default)]
+    pub runs: Vec<Run>,
+}
+
+impl Manifest {
+    /// Loads the manifest at `path`, or an empty one if it does not exist yet
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the manifest cannot be read
+    pub fn load(fs: &dyn FsDriver, path: &Path) -> Result<Self> {
+        if fs.exists(path) {
+            Ok(serde_json::from_str(&fs.read_file(path)?)?)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Saves the manifest to `path`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the manifest cannot be written
+    pub fn save(&self, fs: &dyn FsDriver, path: &Path) -> Result<()> {
+        fs.write_file(path, &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Whether `content` is exactly what was last generated into `file`
+    #[must_use]
+    pub fn is_unchanged(&self, file: &str, content: &[u8]) -> bool {
+        self.files
+            .get(file)
+            .is_some_and(|record| record.hash == hash(content))
+    }
+
+    /// Records `content` as the last generated content of `file`
+    pub fn record(&mut self, file: &str, content: &[u8]) {
+        self.files.insert(
+            file.to_string(),
+            FileRecord {
+                hash: hash(content),
+            },
+        );
+    }
+
+    /// Moves the record of `from` to `to`, following a moved file
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(record) = self.files.remove(from) {
+            self.files.insert(to.to_string(), record);
+        }
+    }
+}
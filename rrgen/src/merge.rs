@@ -0,0 +1,115 @@
+use std::ops::Range;
+
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+
+/// The result of a three-way merge
+pub struct Merged {
+    pub content: String,
+    /// how many conflicts were written with markers
+    pub conflicts: usize,
+}
+
+/// A change to a range of base lines, replacing them with `lines`
+struct Hunk<'a> {
+    base: Range<usize>,
+    lines: &'a [&'a str],
+}
+
+fn hunks<'a>(base: &[&str], changed: &'a [&'a str]) -> Vec<Hunk<'a>> {
+    capture_diff_slices(Algorithm::Myers, base, changed)
+        .iter()
+        .map(similar::DiffOp::as_tag_tuple)
+        .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+        .map(|(_, base, new)| Hunk {
+            base,
+            lines: &changed[new],
+        })
+        .collect()
+}
+
+/// Applies `hunks`, all falling within `range`, to that range of `base`
+fn apply<'a>(base: &[&'a str], range: Range<usize>, hunks: &[&Hunk<'a>]) -> Vec<&'a str> {
+    let mut lines = Vec::new();
+    let mut cursor = range.start;
+    for hunk in hunks {
+        lines.extend_from_slice(&base[cursor..hunk.base.start]);
+        lines.extend_from_slice(hunk.lines);
+        cursor = hunk.base.end;
+    }
+    lines.extend_from_slice(&base[cursor..range.end]);
+    lines
+}
+
+fn push_lines(content: &mut String, lines: &[&str]) {
+    for line in lines {
+        content.push_str(line);
+    }
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs`, line
+/// by line. Where both sides changed the same or adjacent lines differently,
+/// both versions are kept between git-style conflict markers.
+pub fn merge(base: &str, ours: &str, theirs: &str) -> Merged {
+    let base = base.split_inclusive('\n').collect::<Vec<_>>();
+    let ours = ours.split_inclusive('\n').collect::<Vec<_>>();
+    let theirs = theirs.split_inclusive('\n').collect::<Vec<_>>();
+    let our_hunks = hunks(&base, &ours);
+    let their_hunks = hunks(&base, &theirs);
+
+    let mut content = String::new();
+    let mut conflicts = 0;
+    let mut cursor = 0;
+    let (mut o, mut t) = (0, 0);
+    while o < our_hunks.len() || t < their_hunks.len() {
+        // start a group with whichever hunk comes first, then grow it while
+        // hunks from either side overlap or touch it
+        let mut range = match (our_hunks.get(o), their_hunks.get(t)) {
+            (Some(ours), Some(theirs)) if theirs.base.start < ours.base.start => {
+                theirs.base.clone()
+            }
+            (Some(ours), _) => ours.base.clone(),
+            (None, Some(theirs)) => theirs.base.clone(),
+            (None, None) => unreachable!(),
+        };
+        let (o_start, t_start) = (o, t);
+        loop {
+            if let Some(hunk) = our_hunks.get(o).filter(|h| h.base.start <= range.end) {
+                range.end = range.end.max(hunk.base.end);
+                o += 1;
+            } else if let Some(hunk) = their_hunks.get(t).filter(|h| h.base.start <= range.end) {
+                range.end = range.end.max(hunk.base.end);
+                t += 1;
+            } else {
+                break;
+            }
+        }
+
+        content.push_str(&base[cursor..range.start].concat());
+        let ours_in_group = our_hunks[o_start..o].iter().collect::<Vec<_>>();
+        let theirs_in_group = their_hunks[t_start..t].iter().collect::<Vec<_>>();
+        let our_lines = apply(&base, range.clone(), &ours_in_group);
+        let their_lines = apply(&base, range.clone(), &theirs_in_group);
+        if ours_in_group.is_empty() || our_lines == their_lines {
+            content.push_str(&their_lines.concat());
+        } else if theirs_in_group.is_empty() {
+            content.push_str(&our_lines.concat());
+        } else {
+            conflicts += 1;
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str("<<<<<<< current\n");
+            push_lines(&mut content, &our_lines);
+            content.push_str("=======\n");
+            push_lines(&mut content, &their_lines);
+            content.push_str(">>>>>>> generated\n");
+        }
+        cursor = range.end;
+    }
+    content.push_str(&base[cursor..].concat());
+
+    Merged { content, conflicts }
+}
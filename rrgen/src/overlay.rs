@@ -0,0 +1,235 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use similar::TextDiff;
+
+use crate::{Error, FsDriver, Result};
+
+/// A change captured instead of being written
+pub enum Change {
+    Written { content: Vec<u8>, mode: Option<u32> },
+    Removed,
+}
+
+/// Wraps the `FsDriver` so that, while capturing, writes are kept in memory
+/// instead of reaching it. Reads see the captured changes on top of the
+/// underlying files, so generation behaves exactly as it would on disk.
+pub struct Overlay {
+    inner: Box<dyn FsDriver>,
+    layer: RefCell<Option<BTreeMap<PathBuf, Change>>>,
+}
+
+impl Overlay {
+    pub fn new(inner: Box<dyn FsDriver>) -> Self {
+        Self {
+            inner,
+            layer: RefCell::new(None),
+        }
+    }
+
+    /// Starts capturing writes
+    pub fn capture(&self) {
+        self.layer.replace(Some(BTreeMap::new()));
+    }
+
+    /// Stops capturing, returning the captured changes
+    pub fn finish(&self) -> BTreeMap<PathBuf, Change> {
+        self.layer.take().unwrap_or_default()
+    }
+
+    pub fn capturing(&self) -> bool {
+        self.layer.borrow().is_some()
+    }
+
+    fn captured<T>(&self, path: &Path, f: impl FnOnce(&Change) -> T) -> Option<T> {
+        self.layer.borrow().as_ref()?.get(path).map(f)
+    }
+
+    /// Records `change` when capturing, returning whether it was captured
+    fn capture_change(&self, path: &Path, change: impl FnOnce() -> Change) -> bool {
+        self.layer.borrow_mut().as_mut().is_some_and(|layer| {
+            layer.insert(path.to_path_buf(), change());
+            true
+        })
+    }
+
+    /// The content of `path` before any captured change
+    pub fn original(&self, path: &Path) -> Result<Option<Vec<u8>>> {
+        if self.inner.exists(path) {
+            Ok(Some(self.inner.read_bytes(path)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Renders captured changes as a unified patch that `git apply` and
+    /// `patch -p1` accept, naming files with `name`
+    pub fn patch(
+        &self,
+        changes: &BTreeMap<PathBuf, Change>,
+        name: impl Fn(&Path) -> String,
+    ) -> Result<String> {
+        let mut patch = String::new();
+        for (path, change) in changes {
+            let old = self.original(path)?;
+            let (new, mode) = match change {
+                Change::Written { content, mode } => (Some(content), *mode),
+                Change::Removed => (None, None),
+            };
+            let old_mode = if old.is_some() {
+                git_mode(self.inner.mode(path)?)
+            } else {
+                git_mode(None)
+            };
+            let new_mode = mode.map_or(old_mode, |mode| git_mode(Some(mode)));
+            if old.as_ref() == new && old_mode == new_mode {
+                continue;
+            }
+
+            let old_text = text(path, old.as_deref())?;
+            let new_text = text(path, new.map(Vec::as_slice))?;
+
+            let name = name(path);
+            patch.push_str(&format!("diff --git a/{name} b/{name}\n"));
+            let (from, to) = match (&old, new) {
+                (None, _) => {
+                    patch.push_str(&format!("new file mode {new_mode:o}\n"));
+                    ("/dev/null".to_string(), format!("b/{name}"))
+                }
+                (_, None) => {
+                    patch.push_str(&format!("deleted file mode {old_mode:o}\n"));
+                    (format!("a/{name}"), "/dev/null".to_string())
+                }
+                _ => {
+                    if old_mode != new_mode {
+                        patch.push_str(&format!("old mode {old_mode:o}\nnew mode {new_mode:o}\n"));
+                    }
+                    (format!("a/{name}"), format!("b/{name}"))
+                }
+            };
+            patch.push_str(
+                &TextDiff::from_lines(old_text, new_text)
+                    .unified_diff()
+                    .header(&from, &to)
+                    .to_string(),
+            );
+        }
+        Ok(patch)
+    }
+}
+
+/// The mode git records for a file with permissions `mode`: executable or
+/// not
+fn git_mode(mode: Option<u32>) -> u32 {
+    if mode.unwrap_or(0o644) & 0o111 == 0 {
+        0o100_644
+    } else {
+        0o100_755
+    }
+}
+
+/// The text of a file for a patch, empty when it does not exist
+fn text<'a>(path: &Path, content: Option<&'a [u8]>) -> Result<&'a str> {
+    content.map_or(Ok(""), |content| {
+        std::str::from_utf8(content).map_err(|_| {
+            Error::Message(format!(
+                "cannot include binary file {} in a patch",
+                path.display()
+            ))
+        })
+    })
+}
+
+impl FsDriver for Overlay {
+    fn write_file(&self, path: &Path, content: &str) -> Result<()> {
+        self.write_bytes(path, content.as_bytes())
+    }
+
+    fn read_file(&self, path: &Path) -> Result<String> {
+        let content = self.read_bytes(path)?;
+        String::from_utf8(content).map_err(|err| Error::Any(Box::new(err)))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.captured(path, |change| matches!(change, Change::Written { .. }))
+            .unwrap_or_else(|| self.inner.exists(path))
+    }
+
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<()> {
+        if self.capture_change(path, || Change::Written {
+            content: content.to_vec(),
+            mode: None,
+        }) {
+            Ok(())
+        } else {
+            self.inner.write_bytes(path, content)
+        }
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.captured(path, |change| match change {
+            Change::Written { content, .. } => Some(content.clone()),
+            Change::Removed => None,
+        }) {
+            Some(Some(content)) => Ok(content),
+            Some(None) => Err(Error::Message(format!(
+                "cannot read {}: removed",
+                path.display()
+            ))),
+            None => self.inner.read_bytes(path),
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        if self.capture_change(path, || Change::Removed) {
+            Ok(())
+        } else {
+            self.inner.remove_file(path)
+        }
+    }
+
+    fn rename_file(&self, from: &Path, to: &Path) -> Result<()> {
+        if self.capturing() {
+            let content = self.read_bytes(from)?;
+            self.remove_file(from)?;
+            self.write_bytes(to, &content)
+        } else {
+            self.inner.rename_file(from, to)
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn mode(&self, path: &Path) -> Result<Option<u32>> {
+        match self.captured(path, |change| match change {
+            Change::Written { mode, .. } => Some(*mode),
+            Change::Removed => None,
+        }) {
+            Some(Some(Some(mode))) => Ok(Some(mode)),
+            Some(None) => Ok(None),
+            _ if self.inner.exists(path) => self.inner.mode(path),
+            _ => Ok(None),
+        }
+    }
+
+    fn set_mode(&self, path: &Path, mode: u32) -> Result<()> {
+        match self.layer.borrow_mut().as_mut() {
+            Some(layer) => {
+                if let Some(Change::Written { mode: captured, .. }) = layer.get_mut(path) {
+                    *captured = Some(mode);
+                }
+                Ok(())
+            }
+            None => self.inner.set_mode(path, mode),
+        }
+    }
+}
@@ -1,7 +1,17 @@
 use std::{collections::HashMap, hash::BuildHasher};
 
-use cruet::string::pluralize;
-use heck::{ToKebabCase, ToLowerCamelCase, ToSnakeCase, ToTitleCase, ToUpperCamelCase};
+use cruet::{
+    case::{
+        class::to_class_case, pascal::to_pascal_case, sentence::to_sentence_case,
+        snake::to_snake_case, table::to_table_case,
+    },
+    string::{pluralize, singularize},
+    suffix::foreign_key::to_foreign_key,
+};
+use heck::{
+    ToKebabCase, ToLowerCamelCase, ToShoutyKebabCase, ToShoutySnakeCase, ToSnakeCase, ToTitleCase,
+    ToTrainCase, ToUpperCamelCase,
+};
 use tera::{to_value, try_get_value, Result, Tera, Value};
 
 /// Registers all available filters for a given `Tera` instance.
@@ -13,7 +23,21 @@ pub fn register_all(tera: &mut Tera) {
     tera.register_filter("snake_case", snake_case);
     tera.register_filter("title_case", title_case);
     tera.register_filter("upper_case", upper_case);
+    tera.register_filter("screaming_snake_case", screaming_snake_case);
+    tera.register_filter("train_case", train_case);
+    tera.register_filter("shouty_kebab_case", shouty_kebab_case);
+    tera.register_filter("dot_case", dot_case);
+    tera.register_filter("path_case", path_case);
     tera.register_filter("plural", plural);
+    tera.register_filter("singular", singular);
+    tera.register_filter("tableize", tableize);
+    tera.register_filter("classify", classify);
+    tera.register_filter("foreign_key", foreign_key);
+    tera.register_filter("humanize", humanize);
+    tera.register_filter("ordinalize", ordinalize);
+    tera.register_filter("demodulize", demodulize);
+    tera.register_filter("deconstantize", deconstantize);
+    tera.register_filter("camelize", camelize);
 }
 
 pub fn plural<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
@@ -21,6 +45,254 @@ pub fn plural<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> R
     Ok(to_value(pluralize::to_plural(&s)).unwrap())
 }
 
+/// Converts a plural word into its singular, with cruet.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::singular;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "posts");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("singular", singular);
+///
+/// let i = "{{ i | singular }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "post");
+/// ```
+pub fn singular<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("singular", "value", String, value);
+    Ok(to_value(singularize::to_singular(&s)).unwrap())
+}
+
+/// Converts a model name into its table name: plural `snake_case`, with
+/// cruet's `to_table_case`.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::tableize;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "LineItem");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("tableize", tableize);
+///
+/// let i = "{{ i | tableize }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "line_items");
+/// ```
+pub fn tableize<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("tableize", "value", String, value);
+    Ok(to_value(to_table_case(&s)).unwrap())
+}
+
+/// Converts a table name into its model name: singular `PascalCase`, with
+/// cruet's `to_class_case`.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::classify;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "line_items");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("classify", classify);
+///
+/// let i = "{{ i | classify }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "LineItem");
+/// ```
+pub fn classify<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("classify", "value", String, value);
+    Ok(to_value(to_class_case(&s)).unwrap())
+}
+
+/// Converts a model name into the name of a foreign key referencing it, with
+/// cruet's `to_foreign_key`.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::foreign_key;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "Admin::User");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("foreign_key", foreign_key);
+///
+/// let i = "{{ i | foreign_key }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "user_id");
+/// ```
+pub fn foreign_key<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("foreign_key", "value", String, value);
+    Ok(to_value(to_foreign_key(&s)).unwrap())
+}
+
+/// Converts an attribute name into a human readable label, dropping an `_id`
+/// suffix. cruet has no `humanize`, so this drops the suffix and hands the
+/// rest to cruet's `to_sentence_case`.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::humanize;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "author_id");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("humanize", humanize);
+///
+/// let i = "{{ i | humanize }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "Author");
+/// ```
+pub fn humanize<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("humanize", "value", String, value);
+    let s = s.strip_suffix("_id").unwrap_or(&s);
+    Ok(to_value(to_sentence_case(s)).unwrap())
+}
+
+/// Adds the ordinal suffix to a number, given as a number or a string, with
+/// cruet.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::ordinalize;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", &22);
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("ordinalize", ordinalize);
+///
+/// let i = "{{ i | ordinalize }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "22nd");
+/// ```
+pub fn ordinalize<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = match value {
+        Value::Number(number) => number.to_string(),
+        _ => try_get_value!("ordinalize", "value", String, value),
+    };
+    if s.is_empty() {
+        return Ok(to_value(s).unwrap());
+    }
+    Ok(to_value(cruet::number::ordinalize::ordinalize(&s)).unwrap())
+}
+
+/// Drops the module path from a path of `::` separated names, with cruet.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::demodulize;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "Admin::Users::Profile");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("demodulize", demodulize);
+///
+/// let i = "{{ i | demodulize }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "Profile");
+/// ```
+pub fn demodulize<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("demodulize", "value", String, value);
+    Ok(to_value(cruet::string::demodulize::demodulize(&s)).unwrap())
+}
+
+/// Drops the last name from a path of `::` separated names. Unlike cruet's
+/// `deconstantize`, which keeps only the parent name (`Foo` for
+/// `Test::Foo::Bar`), this keeps the whole namespace, as Rails does.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::deconstantize;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "Admin::Users::Profile");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("deconstantize", deconstantize);
+///
+/// let i = "{{ i | deconstantize }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "Admin::Users");
+/// ```
+pub fn deconstantize<S: BuildHasher>(
+    value: &Value,
+    _: &HashMap<String, Value, S>,
+) -> Result<Value> {
+    let s = try_get_value!("deconstantize", "value", String, value);
+    let namespace = s.rsplit_once("::").map_or("", |(namespace, _)| namespace);
+    Ok(to_value(namespace).unwrap())
+}
+
+/// Converts a `/` separated path into `::` separated `PascalCase` names,
+/// keeping the given acronyms in their own casing. cruet has no notion of
+/// acronyms, so each word goes through cruet's `to_pascal_case` unless it
+/// is one of them.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::camelize;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "admin/api_users");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("camelize", camelize);
+///
+/// let i = r#"{{ i | camelize(acronyms=["API"]) }}"#;
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "Admin::APIUsers");
+/// ```
+pub fn camelize<S: BuildHasher>(value: &Value, args: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("camelize", "value", String, value);
+    let acronyms = match args.get("acronyms") {
+        Some(acronyms) => try_get_value!("camelize", "acronyms", Vec<String>, acronyms),
+        None => vec![],
+    };
+    let camelized = s
+        .split('/')
+        .map(|segment| {
+            to_snake_case(segment)
+                .split('_')
+                .map(|word| {
+                    acronyms
+                        .iter()
+                        .find(|acronym| acronym.eq_ignore_ascii_case(word))
+                        .map_or_else(|| to_pascal_case(word), Clone::clone)
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("::");
+    Ok(to_value(camelized).unwrap())
+}
+
 /// Converts text into `PascalCase`.
 ///
 /// # Example
@@ -181,3 +453,124 @@ pub fn upper_case<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>)
     let s = try_get_value!("upper_case", "value", String, value);
     Ok(to_value(s.to_uppercase()).unwrap())
 }
+
+/// Converts text into `SCREAMING_SNAKE_CASE`.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::screaming_snake_case;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "email stats queue");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("screaming_snake_case", screaming_snake_case);
+///
+/// let i = "{{ i | screaming_snake_case }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "EMAIL_STATS_QUEUE");
+/// ```
+pub fn screaming_snake_case<S: BuildHasher>(
+    value: &Value,
+    _: &HashMap<String, Value, S>,
+) -> Result<Value> {
+    let s = try_get_value!("screaming_snake_case", "value", String, value);
+    Ok(to_value(s.to_shouty_snake_case()).unwrap())
+}
+
+/// Converts text into Train-Case.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::train_case;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "content type");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("train_case", train_case);
+///
+/// let i = "{{ i | train_case }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "Content-Type");
+/// ```
+pub fn train_case<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("train_case", "value", String, value);
+    Ok(to_value(s.to_train_case()).unwrap())
+}
+
+/// Converts text into SHOUTY-KEBAB-CASE.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::shouty_kebab_case;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "some text");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("shouty_kebab_case", shouty_kebab_case);
+///
+/// let i = "{{ i | shouty_kebab_case }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "SOME-TEXT");
+/// ```
+pub fn shouty_kebab_case<S: BuildHasher>(
+    value: &Value,
+    _: &HashMap<String, Value, S>,
+) -> Result<Value> {
+    let s = try_get_value!("shouty_kebab_case", "value", String, value);
+    Ok(to_value(s.to_shouty_kebab_case()).unwrap())
+}
+
+/// Converts text into dot.case.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::dot_case;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "someText");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("dot_case", dot_case);
+///
+/// let i = "{{ i | dot_case }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "some.text");
+/// ```
+pub fn dot_case<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("dot_case", "value", String, value);
+    Ok(to_value(s.to_snake_case().replace('_', ".")).unwrap())
+}
+
+/// Converts text into path/case.
+///
+/// # Example
+///
+/// ```ignore
+/// use tera::{Context, Tera};
+/// use tera_text_filters::path_case;
+///
+/// let mut ctx = Context::new();
+/// ctx.insert("i", "someText");
+///
+/// let mut tera = Tera::default();
+/// tera.register_filter("path_case", path_case);
+///
+/// let i = "{{ i | path_case }}";
+/// let rendered = tera.render_str(i, &ctx).unwrap();
+/// assert_eq!(rendered, "some/text");
+/// ```
+pub fn path_case<S: BuildHasher>(value: &Value, _: &HashMap<String, Value, S>) -> Result<Value> {
+    let s = try_get_value!("path_case", "value", String, value);
+    Ok(to_value(s.to_snake_case().replace('_', "/")).unwrap())
+}
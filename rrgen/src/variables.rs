@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{Error, Format, Result};
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum VarType {
+    #[default]
+    String,
+    Bool,
+    Int,
+    List,
+    Enum,
+}
+
+impl VarType {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::List => "list",
+            Self::Enum => "enum",
+        }
+    }
+}
+
+/// A variable declared in the `variables:` section of a template.
+#[derive(Deserialize, Debug, Default)]
+struct Declaration {
+    #[serde(rename = "type")]
+    #[serde(default)]
+    kind: VarType,
+
+    #[serde(default)]
+    required: bool,
+
+    #[serde(default)]
+    default: Option<Value>,
+
+    #[serde(default)]
+    description: Option<String>,
+
+    #[serde(with = "serde_regex")]
+    #[serde(default)]
+    pattern: Option<Regex>,
+
+    #[serde(default)]
+    values: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Declarations {
+    #[serde(default)]
+    variables: BTreeMap<String, Declaration>,
+}
+
+/// Pulls the top level `variables:` block out of raw, unrendered YAML
+/// frontmatter, which may contain Tera elsewhere.
+fn extract_block(frontmatter: &str) -> Option<String> {
+    let mut lines = frontmatter
+        .lines()
+        .skip_while(|line| !line.starts_with("variables:"));
+
+    let first = lines.next()?;
+    let mut block = vec![first];
+    block.extend(
+        lines.take_while(|line| line.is_empty() || line.starts_with(' ') || line.starts_with('#')),
+    );
+    Some(block.join("\n"))
+}
+
+/// Pulls the `[variables]` tables, and top level `variables` keys, out of raw,
+/// unrendered TOML frontmatter.
+fn extract_toml(frontmatter: &str) -> Option<String> {
+    let is_variables = |key: &str| {
+        key.strip_prefix("variables")
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    };
+
+    let mut block = Vec::new();
+    let mut top_level = true;
+    let mut in_variables = false;
+    for line in frontmatter.lines() {
+        let trimmed = line.trim_start();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            top_level = false;
+            in_variables = !header.starts_with('[')
+                && header
+                    .split(']')
+                    .next()
+                    .is_some_and(|name| is_variables(name.trim()));
+            if in_variables {
+                block.push(line);
+            }
+        } else if in_variables
+            || (top_level && trimmed.split(['=', ' ']).next().is_some_and(is_variables))
+        {
+            block.push(line);
+        }
+    }
+    (!block.is_empty()).then(|| block.join("\n"))
+}
+
+/// The index just past the JSON string opening at `start`
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() && bytes[i] != b'"' {
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    i + 1
+}
+
+/// Pulls the top level `"variables"` member out of raw, unrendered JSON
+/// frontmatter, as an object of its own.
+fn extract_json(frontmatter: &str) -> Option<String> {
+    let bytes = frontmatter.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let end = skip_string(bytes, i);
+                let key = frontmatter.get(i + 1..end.saturating_sub(1));
+                i = end;
+                if depth != 1 || key != Some("variables") {
+                    continue;
+                }
+                let rest = frontmatter.get(i..)?.trim_start();
+                let Some(value) = rest.strip_prefix(':') else {
+                    continue;
+                };
+                let start = frontmatter.len() - value.len();
+                let mut end = start;
+                let mut nested = 0;
+                while end < bytes.len() {
+                    match bytes[end] {
+                        b'"' => {
+                            end = skip_string(bytes, end);
+                            continue;
+                        }
+                        b'{' | b'[' => nested += 1,
+                        b'}' | b']' if nested == 0 => break,
+                        b'}' | b']' => nested -= 1,
+                        b',' if nested == 0 => break,
+                        _ => {}
+                    }
+                    end += 1;
+                }
+                return Some(format!(
+                    "{{\"variables\": {}}}",
+                    frontmatter.get(start..end)?
+                ));
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Reads the variables declared in the raw, unrendered frontmatter.
+/// Declarations are read before rendering so that they can be checked before
+/// the template references them, and so must not use Tera.
+fn declarations(format: Format, frontmatter: &str) -> Result<Declarations> {
+    let block = match format {
+        Format::Yaml => extract_block(frontmatter),
+        Format::Toml => extract_toml(frontmatter),
+        Format::Json => extract_json(frontmatter),
+    };
+    block.map_or_else(|| Ok(Declarations::default()), |block| format.parse(&block))
+}
+
+fn check(name: &str, decl: &Declaration, value: &Value) -> Option<String> {
+    let valid = match decl.kind {
+        VarType::String => value.is_string(),
+        VarType::Bool => value.is_boolean(),
+        VarType::Int => value.is_i64() || value.is_u64(),
+        VarType::List => value.is_array(),
+        VarType::Enum => value
+            .as_str()
+            .is_some_and(|s| decl.values.iter().any(|v| v == s)),
+    };
+    if !valid {
+        return Some(if decl.kind == VarType::Enum {
+            format!(
+                "`{name}` must be one of [{}], got {value}",
+                decl.values.join(", ")
+            )
+        } else {
+            format!("`{name}` must be of type {}, got {value}", decl.kind.name())
+        });
+    }
+    if let (Some(pattern), Some(s)) = (&decl.pattern, value.as_str()) {
+        if !pattern.is_match(s) {
+            return Some(format!("`{name}` must match `{pattern}`, got {value}"));
+        }
+    }
+    None
+}
+
+/// Validates `vars` against the variables declared in the template's
+/// `frontmatter`, returning the vars with declared defaults filled in.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidVars`] listing every violation when any of the
+/// vars are missing or invalid
+pub fn apply(format: Format, frontmatter: &str, vars: &Value) -> Result<Value> {
+    let declarations = declarations(format, frontmatter)?;
+    if declarations.variables.is_empty() {
+        return Ok(vars.clone());
+    }
+
+    let mut vars = match vars {
+        Value::Object(map) => map.clone(),
+        _ => serde_json::Map::new(),
+    };
+    let mut errors = Vec::new();
+    for (name, decl) in &declarations.variables {
+        match vars.get(name) {
+            Some(value) => errors.extend(check(name, decl, value)),
+            None => {
+                if let Some(default) = &decl.default {
+                    vars.insert(name.clone(), default.clone());
+                } else if decl.required {
+                    errors.push(decl.description.as_ref().map_or_else(
+                        || format!("`{name}` is required"),
+                        |description| format!("`{name}` is required ({description})"),
+                    ));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(Value::Object(vars))
+    } else {
+        Err(Error::InvalidVars(errors))
+    }
+}
@@ -0,0 +1,3 @@
+to: public/favicon.png
+copy_from: static/favicon.png
+---
@@ -0,0 +1,4 @@
+to: public
+copy_from: static
+overwrite: never
+---
@@ -1,8 +1,15 @@
 #![allow(non_snake_case)]
-use std::fs;
+use std::{cell::RefCell, fs, path::Path};
 
 use fs_extra::{self, dir::CopyOptions};
-use rrgen::RRgen;
+#[cfg(feature = "archive")]
+use rrgen::ArchiveFormat;
+#[cfg(feature = "git")]
+use rrgen::Git;
+use rrgen::{
+    ActionKind, Backup, ConflictResolver, GenResult, InjectionStrategy, Overwrite, RRgen,
+    Resolution, RunKind,
+};
 use serde_json::json;
 
 #[test]
@@ -94,3 +101,1761 @@ fn test_realistic() {
     .unwrap();
     assert!(!dir_diff::is_different(GENERATED, "tests/fixtures/realistic/expected").unwrap());
 }
+
+struct AddHook;
+
+impl InjectionStrategy for AddHook {
+    fn inject(
+        &self,
+        file_content: &str,
+        content: &str,
+        config: &serde_json::Map<String, serde_json::Value>,
+        _vars: &serde_json::Value,
+    ) -> rrgen::Result<String> {
+        let hook = config
+            .get("hook")
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or_default();
+        let marker = format!("fn {hook}(");
+        let mut lines = file_content.lines().collect::<Vec<_>>();
+        if let Some(pos) = lines.iter().position(|ln| ln.contains(&marker)) {
+            lines.insert(pos + 1, content);
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[test]
+fn test_custom_injection_strategy() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add(
+            "app.rs",
+            "impl Hooks for App {\n    fn routes() -> AppRoutes {\n        AppRoutes::new()\n    }\n}",
+        )
+        .create()
+        .expect("create temp file");
+
+    let template = r#"to: controllers/{{name}}.rs
+injections:
+- into: app.rs
+  strategy: loco_hooks
+  hook: routes
+  content: "        // {{name}} routes"
+---
+pub fn routes() {}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root).add_injection_strategy("loco_hooks", AddHook);
+    rgen.generate(template, &json!({"name": "notes"})).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("app.rs")).unwrap(),
+        "impl Hooks for App {\n    fn routes() -> AppRoutes {\n        // notes routes\n        AppRoutes::new()\n    }\n}"
+    );
+}
+
+#[test]
+fn test_unknown_injection_strategy() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("app.rs", "")
+        .create()
+        .expect("create temp file");
+
+    let template = "to: out.rs\ninjections:\n- into: app.rs\n  strategy: nope\n  content: x\n---\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let err = rgen.generate(template, &json!({})).unwrap_err();
+    assert_eq!(err.to_string(), "unknown injection strategy: nope");
+}
+
+#[test]
+fn test_injection_content_from_partial() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("app/models/users.rs", "pub struct Model;\n")
+        .add(
+            "templates/model.t",
+            "to: app/models/{{name}}_ext.rs\ninjections:\n- into: app/models/users.rs\n  append: true\n  content_from: partials/user_impl.t\n---\n",
+        )
+        .add(
+            "templates/partials/user_impl.t",
+            "impl Model {\n    pub fn {{ name | snake_case }}() {}\n}\n",
+        )
+        .create()
+        .expect("create temp file");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate_file(
+        tree_fs.root.join("templates/model.t"),
+        &json!({"name": "Admin"}),
+    )
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("app/models/users.rs")).unwrap(),
+        "pub struct Model;\n\nimpl Model {\n    pub fn admin() {}\n}"
+    );
+}
+
+#[test]
+fn test_when_and_unless_conditions() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("workers/mod.rs", "pub mod downloader;")
+        .add("tasks/mod.rs", "pub mod seed;")
+        .create()
+        .expect("create temp file");
+
+    let template = r"to: workers/{{name}}.rs
+injections:
+- into: workers/mod.rs
+  append: true
+  when: with_worker
+  content: 'pub mod {{name}};'
+- into: tasks/mod.rs
+  append: true
+  unless: with_worker
+  content: 'pub mod {{name}};'
+---
+";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({"name": "report", "with_worker": true}))
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("workers/mod.rs")).unwrap(),
+        "pub mod downloader;\npub mod report;"
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("tasks/mod.rs")).unwrap(),
+        "pub mod seed;"
+    );
+
+    let template = "to: skipped.rs\nwhen: with_worker\n---\n";
+    let result = rgen
+        .generate(template, &json!({"with_worker": false}))
+        .unwrap();
+    let GenResult::Skipped { actions, .. } = result else {
+        panic!("expected skipped result");
+    };
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].path, tree_fs.root.join("skipped.rs"));
+    assert_eq!(
+        actions[0].skipped.as_deref(),
+        Some("when: `with_worker` is false")
+    );
+    assert!(!tree_fs.root.join("skipped.rs").exists());
+}
+
+const DECLARED_TEMPLATE: &str = r#"variables:
+  name:
+    type: string
+    required: true
+    description: the controller name
+    pattern: "^[a-z_]+$"
+  kind:
+    type: enum
+    values: [api, html]
+    default: api
+  with_worker:
+    type: bool
+    default: false
+  port:
+    type: int
+to: controllers/{{name}}_{{kind}}.rs
+---
+worker: {{ with_worker }}
+"#;
+
+#[test]
+fn test_declared_variables_defaults() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(DECLARED_TEMPLATE, &json!({"name": "notes"}))
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("controllers/notes_api.rs")).unwrap(),
+        "worker: false\n"
+    );
+}
+
+#[test]
+fn test_declared_variables_validation() {
+    let rgen = RRgen::default();
+
+    let err = rgen
+        .generate(DECLARED_TEMPLATE, &json!({"kind": "cli", "port": "80"}))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid template variables: `kind` must be one of [api, html], got \"cli\"; `name` is \
+         required (the controller name); `port` must be of type int, got \"80\""
+    );
+
+    let err = rgen
+        .generate(
+            DECLARED_TEMPLATE,
+            &json!({"name": "Notes", "with_worker": "yes"}),
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid template variables: `name` must match `^[a-z_]+$`, got \"Notes\"; `with_worker` \
+         must be of type bool, got \"yes\""
+    );
+}
+
+#[test]
+fn test_frontmatter_vars_and_body_separators() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+
+    let template = r#"to: fixtures/{{ name | snake_case }}.yaml
+vars:
+  struct_name: "{{ name | pascal_case }}"
+  module_path: "models::{{ name | snake_case }}"
+---
+{% if seed -%}
+---
+{% endif -%}
+# {{ module_path }}::{{ struct_name }}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({"name": "user_note", "seed": true}))
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("fixtures/user_note.yaml")).unwrap(),
+        "---\n# models::user_note::UserNote\n"
+    );
+}
+
+#[test]
+fn test_overwrite_policies() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("existing.rs", "hand written")
+        .create()
+        .expect("create temp file");
+    let existing = tree_fs.root.join("existing.rs");
+
+    let template = "to: existing.rs\noverwrite: never\n---\ngenerated {{n}}\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let result = rgen.generate(template, &json!({"n": 1})).unwrap();
+    let GenResult::Skipped { actions, .. } = result else {
+        panic!("expected skipped result");
+    };
+    assert_eq!(actions[0].skipped.as_deref(), Some("exists"));
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "hand written");
+
+    // the global policy wins over the template's
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_overwrite(Overwrite::Always);
+    rgen.generate(template, &json!({"n": 1})).unwrap();
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "generated 1\n");
+}
+
+#[test]
+fn test_overwrite_if_unchanged() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let generated = tree_fs.root.join("generated.rs");
+
+    let template = "to: generated.rs\noverwrite: if_unchanged\n---\ngenerated {{n}}\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_manifest(".rrgen/manifest.json");
+    rgen.generate(template, &json!({"n": 1})).unwrap();
+    rgen.generate(template, &json!({"n": 2})).unwrap();
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "generated 2\n");
+
+    fs::write(&generated, "edited").unwrap();
+    let result = rgen.generate(template, &json!({"n": 3})).unwrap();
+    assert!(matches!(result, GenResult::Skipped { .. }));
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "edited");
+}
+
+struct Accept;
+
+impl ConflictResolver for Accept {
+    fn resolve(&self, _file_to: &Path, existing: &str, generated: &str) -> Resolution {
+        assert_eq!(existing, "hand written");
+        assert_eq!(generated, "generated\n");
+        Resolution::Overwrite
+    }
+}
+
+#[test]
+fn test_overwrite_prompt() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("existing.rs", "hand written")
+        .create()
+        .expect("create temp file");
+    let existing = tree_fs.root.join("existing.rs");
+    let template = "to: existing.rs\noverwrite: prompt\n---\ngenerated\n";
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let result = rgen.generate(template, &json!({})).unwrap();
+    assert!(matches!(result, GenResult::Skipped { .. }));
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "hand written");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_conflict_resolver(Accept);
+    rgen.generate(template, &json!({})).unwrap();
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "generated\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_file_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+
+    for (mode, expected) in [("0755", 0o755), ("'0700'", 0o700), ("'0o750'", 0o750)] {
+        let template = format!("to: bin/run.sh\nmode: {mode}\n---\n#!/bin/sh\n");
+        rgen.generate(&template, &json!({})).unwrap();
+        let metadata = fs::metadata(tree_fs.root.join("bin/run.sh")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, expected, "{mode}");
+    }
+}
+
+#[test]
+fn test_copy_from_verbatim() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("public/fonts/icons.woff", "customized")
+        .create()
+        .expect("create temp file");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate_file("tests/fixtures/assets/favicon.t", &json!({}))
+        .unwrap();
+    assert_eq!(
+        fs::read(tree_fs.root.join("public/favicon.png")).unwrap(),
+        fs::read("tests/fixtures/assets/static/favicon.png").unwrap()
+    );
+
+    // folders are copied file by file, respecting the overwrite policy
+    fs::remove_file(tree_fs.root.join("public/favicon.png")).unwrap();
+    rgen.generate_file("tests/fixtures/assets/static_dir.t", &json!({}))
+        .unwrap();
+    assert_eq!(
+        fs::read(tree_fs.root.join("public/favicon.png")).unwrap(),
+        fs::read("tests/fixtures/assets/static/favicon.png").unwrap()
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("public/fonts/icons.woff")).unwrap(),
+        "customized"
+    );
+}
+
+#[test]
+fn test_copy_from_dot_relative_template() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate_file("./tests/fixtures/assets/static_dir.t", &json!({}))
+        .unwrap();
+    assert_eq!(
+        fs::read(tree_fs.root.join("public/favicon.png")).unwrap(),
+        fs::read("tests/fixtures/assets/static/favicon.png").unwrap()
+    );
+    assert_eq!(
+        fs::read(tree_fs.root.join("public/fonts/icons.woff")).unwrap(),
+        fs::read("tests/fixtures/assets/static/fonts/icons.woff").unwrap()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_hook_commands() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+
+    let template = r#"to: src/{{name}}.rs
+before:
+- "touch before.txt"
+after:
+- "cat src/{{name}}.rs"
+---
+fn {{name}}() {}
+"#;
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let result = rgen.generate(template, &json!({"name": "hello"})).unwrap();
+    let GenResult::Generated { commands, .. } = result else {
+        panic!("expected generated result");
+    };
+    assert!(commands.is_empty());
+    assert!(!tree_fs.root.join("before.txt").exists());
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root).allow_commands(true);
+    let result = rgen.generate(template, &json!({"name": "hello"})).unwrap();
+    let GenResult::Generated { commands, .. } = result else {
+        panic!("expected generated result");
+    };
+    assert!(tree_fs.root.join("before.txt").exists());
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[1].command, "cat src/hello.rs");
+    assert_eq!(commands[1].stdout, "fn hello() {}\n");
+
+    // before hooks that ran are reported even when the template is skipped
+    let result = rgen
+        .generate(
+            "to: src/hello.rs\nskip_exists: true\nbefore:\n- echo checked\n---\n",
+            &json!({}),
+        )
+        .unwrap();
+    let GenResult::Skipped { commands, .. } = result else {
+        panic!("expected skipped result");
+    };
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].stdout, "checked\n");
+
+    let err = rgen
+        .generate("to: x.rs\nbefore:\n- exit 3\n---\n", &json!({}))
+        .unwrap_err();
+    assert!(err.to_string().starts_with("command `exit 3` failed"));
+}
+
+#[test]
+fn test_generated_actions() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("controllers/mod.rs", "pub mod auth;")
+        .add("app.rs", "AppRoutes::with_default_routes()")
+        .create()
+        .expect("create temp file");
+
+    let template = r#"to: controllers/{{name}}.rs
+injections:
+- into: controllers/mod.rs
+  append: true
+  content: "pub mod {{name}};"
+- into: app.rs
+  after: "AppRoutes::"
+  skip_if: "{{name}}::routes"
+  content: "    .add_route(controllers::{{name}}::routes())"
+---
+pub fn routes() {}
+pub fn {{name}}() {}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({"name": "notes"})).unwrap();
+    let GenResult::Generated { actions, .. } =
+        rgen.generate(template, &json!({"name": "notes"})).unwrap()
+    else {
+        panic!("expected generated result");
+    };
+
+    let summary = actions
+        .iter()
+        .map(|action| {
+            (
+                action
+                    .path
+                    .strip_prefix(&tree_fs.root)
+                    .unwrap()
+                    .to_path_buf(),
+                action.kind,
+                action.skipped.clone(),
+                action.bytes,
+                action.lines,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "controllers/notes.rs".into(),
+                ActionKind::Overwritten,
+                None,
+                37,
+                2
+            ),
+            (
+                "controllers/mod.rs".into(),
+                ActionKind::Injected,
+                None,
+                14,
+                1
+            ),
+            (
+                "app.rs".into(),
+                ActionKind::Injected,
+                Some("skip_if matched".to_string()),
+                0,
+                0
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_frontmatter_formats() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("app.rs", "let routes = [\n];")
+        .create()
+        .expect("create temp file");
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let vars = json!({"name": "notes"});
+
+    let yaml = "---\nto: yaml/{{name}}.rs\n---\nyaml body\n";
+    let toml = r#"+++
+to = "toml/{{name}}.rs"
+
+[[injections]]
+into = "app.rs"
+before_last = '\]'
+content = '    "{{name}}",'
+
+[variables.name]
+type = "string"
+required = true
++++
+toml body
+"#;
+    let json = "{\n  \"to\": \"json/{{name}}.rs\"\n}\njson body\n";
+    let json_separated = "{\"to\": \"json/{{name}}_separated.rs\"}\n---\njson body\n";
+
+    for template in [yaml, toml, json, json_separated] {
+        rgen.generate(template, &vars).unwrap();
+    }
+
+    for (file, body) in [
+        ("yaml/notes.rs", "yaml body\n"),
+        ("toml/notes.rs", "toml body\n"),
+        ("json/notes.rs", "json body\n"),
+        ("json/notes_separated.rs", "json body\n"),
+        ("app.rs", "let routes = [\n    \"notes\",\n];"),
+    ] {
+        assert_eq!(
+            fs::read_to_string(tree_fs.root.join(file)).unwrap(),
+            body,
+            "{file}"
+        );
+    }
+
+    let err = rgen.generate(toml, &json!({})).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid template variables: `name` is required"
+    );
+}
+
+#[test]
+fn test_declared_variables_beside_tera() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+
+    // "variables" mentioned outside a declaration, next to Tera tags
+    let toml = r#"+++
+to = "{% if admin %}admin/{% endif %}{{name}}.rs"
+message = "no variables declared for {{name}}"
+{% if admin %}
+skip_exists = true
+{% endif %}
++++
+toml body
+"#;
+    let json = "{\n  \"to\": \"{% if admin %}admin/{% endif %}{{name}}.json\",\n  \"message\": \"variables\",\n  \"skip_exists\": {{ admin }}\n}\njson body\n";
+    for template in [toml, json] {
+        rgen.generate(template, &json!({"name": "notes", "admin": true}))
+            .unwrap();
+    }
+    assert!(tree_fs.root.join("admin/notes.rs").exists());
+    assert!(tree_fs.root.join("admin/notes.json").exists());
+
+    // declarations are read from their own section only
+    let toml = r#"+++
+to = "{% if admin %}admin/{% endif %}{{name}}.rs"
+
+{% if admin %}
+skip_exists = true
+{% endif %}
+
+[variables.name]
+type = "string"
+required = true
+
+[[injections]]
+into = "{{name}}.rs"
+append = true
+content = "{{name}}"
++++
+"#;
+    let json = "{\n  \"to\": \"{% if admin %}admin/{% endif %}{{name}}.json\",\n  \"variables\": {\"name\": {\"required\": true}},\n  \"skip_exists\": {{ admin }}\n}\n";
+    for template in [toml, json] {
+        assert_eq!(
+            rgen.generate(template, &json!({"admin": true}))
+                .unwrap_err()
+                .to_string(),
+            "invalid template variables: `name` is required"
+        );
+    }
+}
+
+#[test]
+fn test_injection_only_template() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("Cargo.toml", "[dependencies]\nserde = \"1\"")
+        .create()
+        .expect("create temp file");
+
+    let template = r#"message: "added {{name}}"
+injections:
+- into: Cargo.toml
+  after: "\\[dependencies\\]"
+  content: '{{name}} = "{{version}}"'
+---
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let result = rgen
+        .generate(template, &json!({"name": "tokio", "version": "1"}))
+        .unwrap();
+    let GenResult::Generated {
+        message, actions, ..
+    } = result
+    else {
+        panic!("expected generated result");
+    };
+    assert_eq!(message.as_deref(), Some("added tokio"));
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].kind, ActionKind::Injected);
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("Cargo.toml")).unwrap(),
+        "[dependencies]\ntokio = \"1\"\nserde = \"1\""
+    );
+}
+
+#[test]
+fn test_delete_and_move() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("controllers/mod.rs", "pub mod auth;\npub mod notes;")
+        .add("controllers/notes.rs", "// notes")
+        .add("views/notes.rs", "// view")
+        .add("models/users.rs", "// users")
+        .create()
+        .expect("create temp file");
+
+    let template = r"move:
+- from: controllers/{{from}}.rs
+  to: controllers/{{to}}.rs
+- from: models/users.rs
+  to: models/users/mod.rs
+delete:
+- views/{{from}}.rs
+- views/missing.rs
+injections:
+- into: controllers/mod.rs
+  remove_lines: 'pub mod {{from}};'
+- into: controllers/mod.rs
+  append: true
+  content: 'pub mod {{to}};'
+---
+";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let result = rgen
+        .generate(template, &json!({"from": "notes", "to": "memos"}))
+        .unwrap();
+    let GenResult::Generated { actions, .. } = result else {
+        panic!("expected generated result");
+    };
+    let kinds = actions
+        .iter()
+        .map(|action| (action.kind, action.skipped.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            (ActionKind::Moved, None),
+            (ActionKind::Moved, None),
+            (ActionKind::Deleted, None),
+            (ActionKind::Deleted, Some("does not exist".to_string())),
+            (ActionKind::Injected, None),
+            (ActionKind::Injected, None),
+        ]
+    );
+
+    assert!(!tree_fs.root.join("controllers/notes.rs").exists());
+    assert!(!tree_fs.root.join("views/notes.rs").exists());
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("controllers/memos.rs")).unwrap(),
+        "// notes"
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("models/users/mod.rs")).unwrap(),
+        "// users"
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("controllers/mod.rs")).unwrap(),
+        "pub mod auth;\npub mod memos;"
+    );
+}
+
+#[test]
+fn test_delete_done_before_still_injects() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("views/mod.rs", "mod index;")
+        .create()
+        .expect("create temp file");
+
+    let template = "delete:\n- views/old.rs\ninjections:\n- into: views/mod.rs\n  append: true\n  content: mod new;\n---\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let GenResult::Generated { actions, .. } = rgen.generate(template, &json!({})).unwrap() else {
+        panic!("expected generated result");
+    };
+    assert_eq!(actions[0].skipped.as_deref(), Some("does not exist"));
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("views/mod.rs")).unwrap(),
+        "mod index;\nmod new;"
+    );
+}
+
+#[test]
+fn test_move_respects_overwrite_policy() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("a.rs", "// a")
+        .add("b.rs", "// b")
+        .create()
+        .expect("create temp file");
+
+    let template = "overwrite: never\nmove:\n- from: a.rs\n  to: b.rs\ndelete:\n- b.rs\n---\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({})).unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("a.rs")).unwrap(),
+        "// a"
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("b.rs")).unwrap(),
+        "// b"
+    );
+}
+
+#[test]
+fn test_destroy() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let FROM = "tests/fixtures/realistic/app";
+    let GENERATED = "tests/fixtures/realistic/generated";
+    fs_extra::dir::copy(
+        FROM,
+        tree_fs.root.join(GENERATED),
+        &CopyOptions {
+            copy_inside: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    let vars = json!({"name": "email_stats"});
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    for template in [
+        "tests/fixtures/realistic/controller.t",
+        "tests/fixtures/realistic/task.t",
+    ] {
+        rgen.generate_file(template, &vars).unwrap();
+    }
+    for template in [
+        "tests/fixtures/realistic/controller.t",
+        "tests/fixtures/realistic/task.t",
+    ] {
+        let GenResult::Generated { actions, .. } = rgen.destroy_file(template, &vars).unwrap()
+        else {
+            panic!("expected generated result");
+        };
+        assert!(actions.iter().all(|action| action.skipped.is_none()));
+    }
+
+    let generated = tree_fs.root.join(GENERATED);
+    assert!(!generated.join("controllers/email_stats.rs").exists());
+    assert!(!generated.join("tasks/email_stats.rs").exists());
+    // line based injections do not keep a trailing newline, which destroy
+    // cannot bring back
+    for file in ["controllers/mod.rs", "tasks/mod.rs", "app.rs"] {
+        assert_eq!(
+            fs::read_to_string(generated.join(file)).unwrap().trim_end(),
+            fs::read_to_string(Path::new(FROM).join(file))
+                .unwrap()
+                .trim_end(),
+            "{file}"
+        );
+    }
+}
+
+#[test]
+fn test_destroy_leaves_unmatched_content() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add(
+            "controllers/mod.rs",
+            "pub mod auth;\npub mod notes; // edited",
+        )
+        .create()
+        .expect("create temp file");
+
+    let template = "injections:\n- into: controllers/mod.rs\n  append: true\n  content: 'pub mod {{name}};'\n---\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let GenResult::Generated { actions, .. } =
+        rgen.destroy(template, &json!({"name": "notes"})).unwrap()
+    else {
+        panic!("expected generated result");
+    };
+    assert_eq!(actions[0].kind, ActionKind::Reverted);
+    assert_eq!(actions[0].skipped.as_deref(), Some("content not found"));
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("controllers/mod.rs")).unwrap(),
+        "pub mod auth;\npub mod notes; // edited"
+    );
+}
+
+#[test]
+fn test_destroy_leaves_modified_files() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("ctrl.rs", "hand edited heavily\n")
+        .create()
+        .expect("create temp file");
+
+    let template = "to: ctrl.rs\n---\ngenerated\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let GenResult::Generated { actions, .. } = rgen.destroy(template, &json!({})).unwrap() else {
+        panic!("expected generated result");
+    };
+    assert_eq!(actions[0].kind, ActionKind::Deleted);
+    assert_eq!(
+        actions[0].skipped.as_deref(),
+        Some("modified since generated")
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("ctrl.rs")).unwrap(),
+        "hand edited heavily\n"
+    );
+
+    // with a manifest, the file is compared against what was generated
+    let template = "to: model.rs\n---\ngenerated {{n}}\n";
+    let rgen = rgen.with_manifest(".rrgen/manifest.json");
+    rgen.generate(template, &json!({"n": 1})).unwrap();
+    rgen.destroy(template, &json!({"n": 2})).unwrap();
+    assert!(!tree_fs.root.join("model.rs").exists());
+
+    rgen.generate(template, &json!({"n": 1})).unwrap();
+    fs::write(tree_fs.root.join("model.rs"), "generated 1\nedited\n").unwrap();
+    rgen.destroy(template, &json!({"n": 1})).unwrap();
+    assert!(tree_fs.root.join("model.rs").exists());
+
+    // unless forced
+    let rgen = rgen.with_overwrite(Overwrite::Always);
+    rgen.destroy(template, &json!({"n": 1})).unwrap();
+    assert!(!tree_fs.root.join("model.rs").exists());
+}
+
+#[test]
+fn test_manifest_journal() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "mod a;")
+        .create()
+        .expect("create temp file");
+
+    let template = r#"---
+to: src/{{name}}.rs
+injections:
+- into: src/lib.rs
+  append: true
+  content: "mod {{name}};"
+---
+fn {{name}}() {}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_manifest(".rrgen/manifest.json");
+    assert!(RRgen::default().read_manifest().unwrap().is_none());
+    rgen.generate(template, &json!({"name": "b"})).unwrap();
+    rgen.destroy(template, &json!({"name": "b"})).unwrap();
+
+    let manifest = rgen.read_manifest().unwrap().unwrap();
+    assert_eq!(manifest.runs.len(), 2);
+    let (generate, destroy) = (&manifest.runs[0], &manifest.runs[1]);
+    assert_eq!(generate.kind, RunKind::Generate);
+    assert!(generate.template.starts_with("sha256:"));
+    assert_eq!(generate.vars, json!({"name": "b"}));
+    assert_eq!(generate.files.len(), 1);
+    assert_eq!(generate.files[0].path, "src/b.rs");
+    assert_eq!(generate.files[0].action, ActionKind::Created);
+    assert!(generate.files[0].hash.is_some());
+    assert_eq!(generate.injections.len(), 1);
+    assert_eq!(generate.injections[0].path, "src/lib.rs");
+    assert_eq!(generate.injections[0].line, Some(2));
+    assert_eq!(generate.injections[0].content, "mod b;");
+
+    assert_eq!(destroy.kind, RunKind::Destroy);
+    assert_ne!(generate.id, destroy.id);
+    assert_eq!(destroy.files[0].action, ActionKind::Deleted);
+    assert!(destroy.files[0].hash.is_none());
+    assert_eq!(destroy.injections[0].line, Some(2));
+}
+
+#[test]
+fn test_hand_edited_files_are_not_overwritten() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("existing.rs", "hand written")
+        .create()
+        .expect("create temp file");
+    let generated = tree_fs.root.join("generated.rs");
+    let existing = tree_fs.root.join("existing.rs");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_manifest(".rrgen/manifest.json");
+    let template = "to: generated.rs\n---\ngenerated {{n}}\n";
+    rgen.generate(template, &json!({"n": 1})).unwrap();
+    rgen.generate(template, &json!({"n": 2})).unwrap();
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "generated 2\n");
+
+    // files rrgen has no record of are overwritten as before
+    rgen.generate("to: existing.rs\n---\ngenerated\n", &json!({}))
+        .unwrap();
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "generated\n");
+
+    fs::write(&generated, "customized").unwrap();
+    let result = rgen.generate(template, &json!({"n": 3})).unwrap();
+    assert!(matches!(result, GenResult::Skipped { .. }));
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "customized");
+
+    let rgen = rgen.with_overwrite(Overwrite::Always);
+    rgen.generate(template, &json!({"n": 3})).unwrap();
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "generated 3\n");
+}
+
+#[test]
+fn test_unless_modified_without_manifest() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let generated = tree_fs.root.join("generated.rs");
+    let template = "to: generated.rs\n---\ngenerated {{n}}\n";
+
+    // with nothing to compare against, hand edits are overwritten
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({"n": 1})).unwrap();
+    fs::write(&generated, "customized").unwrap();
+    let GenResult::Generated { actions, .. } = rgen.generate(template, &json!({"n": 2})).unwrap()
+    else {
+        panic!("expected generated result");
+    };
+    assert_eq!(actions[0].kind, ActionKind::Overwritten);
+    assert_eq!(actions[0].skipped, None);
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "generated 2\n");
+
+    // while if_unchanged refuses any file it cannot vouch for
+    fs::write(&generated, "customized").unwrap();
+    let rgen = rgen.with_overwrite(Overwrite::IfUnchanged);
+    let GenResult::Skipped { actions, .. } = rgen.generate(template, &json!({"n": 3})).unwrap()
+    else {
+        panic!("expected skipped result");
+    };
+    assert_eq!(
+        actions[0].skipped.as_deref(),
+        Some("no record of generating it")
+    );
+    assert_eq!(fs::read_to_string(&generated).unwrap(), "customized");
+}
+
+#[test]
+fn test_overwrite_merge() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let generated = tree_fs.root.join("service.rs");
+    let rgen = RRgen::with_working_dir(&tree_fs.root)
+        .with_manifest(".rrgen/manifest.json")
+        .with_overwrite(Overwrite::Merge);
+
+    let v1 = "to: service.rs\n---\nuse a;\n\nfn start() {}\n\nfn stop() {}\n";
+    rgen.generate(v1, &json!({})).unwrap();
+    fs::write(
+        &generated,
+        "use a;\n\nfn start() { custom() }\n\nfn stop() {}\n",
+    )
+    .unwrap();
+
+    // the template changes other lines: both changes are kept
+    let v2 = "to: service.rs\n---\nuse a;\nuse b;\n\nfn start() {}\n\nfn stop() {}\n";
+    let GenResult::Generated { actions, .. } = rgen.generate(v2, &json!({})).unwrap() else {
+        panic!("expected a generated result");
+    };
+    assert_eq!(actions[0].kind, ActionKind::Merged);
+    assert_eq!(
+        fs::read_to_string(&generated).unwrap(),
+        "use a;\nuse b;\n\nfn start() { custom() }\n\nfn stop() {}\n"
+    );
+
+    // the template changes the edited line: a conflict is written
+    let v3 = "to: service.rs\n---\nuse a;\nuse b;\n\nfn start() { run() }\n\nfn stop() {}\n";
+    let GenResult::Generated { actions, .. } = rgen.generate(v3, &json!({})).unwrap() else {
+        panic!("expected a generated result");
+    };
+    assert_eq!(actions[0].kind, ActionKind::Conflicted);
+    assert_eq!(
+        fs::read_to_string(&generated).unwrap(),
+        "use a;\nuse b;\n\n<<<<<<< current\nfn start() { custom() }\n=======\nfn start() { run() \
+         }\n>>>>>>> generated\n\nfn stop() {}\n"
+    );
+
+    // an unmodified file is simply overwritten
+    fs::write(
+        &generated,
+        "use a;\nuse b;\n\nfn start() { run() }\n\nfn stop() {}\n",
+    )
+    .unwrap();
+    let v4 = "to: service.rs\n---\nuse a;\n\nfn start() { run() }\n";
+    let GenResult::Generated { actions, .. } = rgen.generate(v4, &json!({})).unwrap() else {
+        panic!("expected a generated result");
+    };
+    assert_eq!(actions[0].kind, ActionKind::Overwritten);
+    assert_eq!(
+        fs::read_to_string(&generated).unwrap(),
+        "use a;\n\nfn start() { run() }\n"
+    );
+}
+
+/// Gives scripted answers, in order
+struct Scripted(RefCell<Vec<Resolution>>);
+
+impl ConflictResolver for Scripted {
+    fn resolve(&self, _file_to: &Path, _existing: &str, _generated: &str) -> Resolution {
+        self.0.borrow_mut().remove(0)
+    }
+}
+
+#[test]
+fn test_conflict_resolutions() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("a.rs", "hand written")
+        .add("b.rs", "hand written")
+        .add("c.rs", "hand written")
+        .create()
+        .expect("create temp file");
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root)
+        .with_overwrite(Overwrite::Prompt)
+        .with_conflict_resolver(Scripted(RefCell::new(vec![
+            Resolution::Diff,
+            Resolution::Abort,
+            Resolution::OverwriteAll,
+        ])));
+    let err = rgen.generate("to: a.rs\n---\ngenerated\n", &json!({}));
+    assert!(matches!(err, Err(rrgen::Error::Aborted)));
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("a.rs")).unwrap(),
+        "hand written"
+    );
+
+    // once answered, `OverwriteAll` applies without asking again
+    for file in ["a.rs", "b.rs", "c.rs"] {
+        rgen.generate(&format!("to: {file}\n---\ngenerated\n"), &json!({}))
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(tree_fs.root.join(file)).unwrap(),
+            "generated\n"
+        );
+    }
+}
+
+#[test]
+fn test_backups() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("existing.rs", "hand written")
+        .add("mod.rs", "mod a;")
+        .create()
+        .expect("create temp file");
+    let template = r#"---
+to: existing.rs
+injections:
+- into: mod.rs
+  append: true
+  content: "mod {{n}};"
+---
+generated {{n}}
+"#;
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_backups(Backup::Sibling);
+    rgen.generate(template, &json!({"n": "b"})).unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("existing.rs.orig")).unwrap(),
+        "hand written"
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("mod.rs.orig")).unwrap(),
+        "mod a;"
+    );
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root)
+        .with_manifest(".rrgen/manifest.json")
+        .with_overwrite(Overwrite::Always)
+        .with_backups(Backup::Dir(".rrgen/backup".into()));
+    rgen.generate(template, &json!({"n": "c"})).unwrap();
+    rgen.generate(template, &json!({"n": "d"})).unwrap();
+
+    let manifest = rgen.read_manifest().unwrap().unwrap();
+    let run = &manifest.runs[1];
+    assert_eq!(
+        run.backups["existing.rs"].path,
+        format!(".rrgen/backup/{}/existing.rs", run.id)
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join(&run.backups["existing.rs"].path)).unwrap(),
+        "generated c\n"
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join(&run.backups["mod.rs"].path)).unwrap(),
+        "mod a;\nmod b;\nmod c;"
+    );
+
+    // back to back runs without a manifest keep their backups apart
+    let unjournaled = RRgen::with_working_dir(&tree_fs.root)
+        .with_overwrite(Overwrite::Always)
+        .with_backups(Backup::Dir("backup".into()));
+    unjournaled.generate(template, &json!({"n": "e"})).unwrap();
+    unjournaled.generate(template, &json!({"n": "f"})).unwrap();
+    let mut backups = fs::read_dir(tree_fs.root.join("backup"))
+        .unwrap()
+        .map(|entry| fs::read_to_string(entry.unwrap().path().join("existing.rs")).unwrap())
+        .collect::<Vec<_>>();
+    backups.sort();
+    assert_eq!(backups, vec!["generated d\n", "generated e\n"]);
+
+    fs::create_dir_all(tree_fs.root.join(".rrgen/backup/notes")).unwrap();
+    let pruned = rgen.prune_backups(1).unwrap();
+    assert_eq!(
+        pruned,
+        vec![tree_fs
+            .root
+            .join(".rrgen/backup")
+            .join(&manifest.runs[0].id)]
+    );
+    assert!(tree_fs.root.join(".rrgen/backup").join(&run.id).exists());
+    assert!(tree_fs.root.join(".rrgen/backup/notes").exists());
+}
+
+#[test]
+fn test_undo_last() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("existing.rs", "hand written")
+        .add("mod.rs", "mod a;")
+        .create()
+        .expect("create temp file");
+    let template = r#"---
+to: {{n}}.rs
+injections:
+- into: mod.rs
+  append: true
+  content: "mod {{n}};"
+---
+generated {{n}}
+"#;
+    let overwrite = "to: existing.rs\n---\ngenerated\n";
+
+    // without backups, injections are removed line by line
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_manifest(".rrgen/manifest.json");
+    rgen.generate(template, &json!({"n": "b"})).unwrap();
+    assert!(tree_fs.root.join("b.rs").exists());
+    rgen.undo_last().unwrap();
+    assert!(!tree_fs.root.join("b.rs").exists());
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("mod.rs")).unwrap(),
+        "mod a;"
+    );
+
+    // overwritten files need a backup
+    rgen.generate(overwrite, &json!({})).unwrap();
+    let err = rgen.undo_last().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "cannot undo run {}: no backup of: existing.rs",
+            rgen.read_manifest().unwrap().unwrap().runs[0].id
+        )
+    );
+
+    let rgen = rgen
+        .with_backups(Backup::Dir(".rrgen/backup".into()))
+        .with_overwrite(Overwrite::Always);
+    fs::write(tree_fs.root.join("existing.rs"), "hand written").unwrap();
+    rgen.generate(overwrite, &json!({})).unwrap();
+    rgen.generate(template, &json!({"n": "c"})).unwrap();
+
+    // files modified since the run are never touched
+    fs::write(tree_fs.root.join("c.rs"), "edited").unwrap();
+    let err = rgen.undo_last().unwrap_err();
+    assert!(err.to_string().ends_with("modified since: c.rs"));
+    fs::write(tree_fs.root.join("c.rs"), "generated c\n").unwrap();
+
+    rgen.undo_last().unwrap();
+    assert!(!tree_fs.root.join("c.rs").exists());
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("mod.rs")).unwrap(),
+        "mod a;"
+    );
+    rgen.undo_last().unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("existing.rs")).unwrap(),
+        "hand written"
+    );
+}
+
+#[test]
+fn test_undo_checks_backups() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("existing.rs", "v0")
+        .create()
+        .expect("create temp file");
+    let template = "to: existing.rs\n---\n{{v}}";
+
+    // a later run replaces the sibling backup of an earlier one
+    let rgen = RRgen::with_working_dir(&tree_fs.root)
+        .with_manifest(".rrgen/manifest.json")
+        .with_backups(Backup::Sibling)
+        .with_overwrite(Overwrite::Always);
+    rgen.generate(template, &json!({"v": "v1"})).unwrap();
+    rgen.generate(template, &json!({"v": "v2"})).unwrap();
+    rgen.undo_last().unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("existing.rs")).unwrap(),
+        "v1"
+    );
+    let err = rgen.undo_last().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "cannot undo run {}: backup replaced since: existing.rs",
+            rgen.read_manifest().unwrap().unwrap().runs[0].id
+        )
+    );
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("existing.rs")).unwrap(),
+        "v1"
+    );
+
+    // pruned backups are reported as missing
+    let rgen = rgen.with_backups(Backup::Dir(".rrgen/backup".into()));
+    fs::remove_file(tree_fs.root.join(".rrgen/manifest.json")).unwrap();
+    rgen.generate(template, &json!({"v": "v3"})).unwrap();
+    fs::remove_dir_all(tree_fs.root.join(".rrgen/backup")).unwrap();
+    let err = rgen.undo_last().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "cannot undo run {}: no backup of: existing.rs",
+            rgen.read_manifest().unwrap().unwrap().runs[0].id
+        )
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_undo_after_hooks() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    // the hook stands in for a formatter rewriting the generated file
+    let template = r#"to: src/{{name}}.rs
+after:
+- "echo '// formatted' >> src/{{name}}.rs"
+---
+fn {{name}}() {}
+"#;
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root)
+        .with_manifest(".rrgen/manifest.json")
+        .allow_commands(true);
+    rgen.generate(template, &json!({"name": "hello"})).unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("src/hello.rs")).unwrap(),
+        "fn hello() {}\n// formatted\n"
+    );
+    rgen.undo_last().unwrap();
+    assert!(!tree_fs.root.join("src/hello.rs").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_undo_injections() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "mod a;\n")
+        .create()
+        .expect("create temp file");
+    // the hook stands in for a formatter adding a header above the injection
+    let template = r#"to: src/{{name}}.rs
+injections:
+- into: src/lib.rs
+  append: true
+  content: "mod {{name}};"
+- into: src/lib.rs
+  after: "^// routes"
+  content: "route!({{name}});"
+after:
+- "printf '// header\\n' | cat - src/lib.rs > lib.tmp && mv lib.tmp src/lib.rs"
+---
+fn {{name}}() {}
+"#;
+
+    let rgen = RRgen::with_working_dir(&tree_fs.root)
+        .with_manifest(".rrgen/manifest.json")
+        .allow_commands(true);
+    let result = rgen.generate(template, &json!({"name": "b"})).unwrap();
+    let GenResult::Generated { actions, .. } = result else {
+        panic!("expected generated");
+    };
+    assert_eq!(actions[2].skipped.as_deref(), Some("no injection made"));
+
+    let manifest = rgen.read_manifest().unwrap().unwrap();
+    let injections = &manifest.runs[0].injections;
+    assert_eq!(injections.len(), 1);
+    assert_eq!(injections[0].line, Some(4));
+
+    rgen.undo_last().unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("src/lib.rs")).unwrap(),
+        "// header\nmod a;\n"
+    );
+}
+
+#[test]
+fn test_generate_patch() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "mod a;\n")
+        .add("src/old.rs", "old\n")
+        .create()
+        .expect("create temp file");
+    let template = r#"---
+to: src/{{name}}.rs
+delete:
+- src/old.rs
+injections:
+- into: src/lib.rs
+  append: true
+  content: "mod {{name}};"
+---
+fn {{name}}() {}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_manifest(".rrgen/manifest.json");
+    let patch = rgen
+        .generate_patch(template, &json!({"name": "b"}))
+        .unwrap();
+    assert_eq!(
+        patch,
+        r"diff --git a/src/b.rs b/src/b.rs
+new file mode 100644
+--- /dev/null
++++ b/src/b.rs
+@@ -0,0 +1 @@
++fn b() {}
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1,3 @@
+ mod a;
++
++mod b;
+\ No newline at end of file
+diff --git a/src/old.rs b/src/old.rs
+deleted file mode 100644
+--- a/src/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-old
+"
+    );
+
+    // nothing was written
+    assert!(!tree_fs.root.join("src/b.rs").exists());
+    assert!(!tree_fs.root.join(".rrgen").exists());
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("src/lib.rs")).unwrap(),
+        "mod a;\n"
+    );
+}
+
+#[test]
+fn test_generate_patch_file() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "pub struct Model;\n")
+        .add(
+            "templates/model.t",
+            "to: src/{{name}}.rs\ninjections:\n- into: src/lib.rs\n  append: true\n  content_from: partials/impl.t\n---\nfn {{name}}() {}\n",
+        )
+        .add("templates/partials/impl.t", "impl Model {}\n")
+        .create()
+        .expect("create temp file");
+
+    // the partial is found next to the template
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let patch = rgen
+        .generate_patch_file(
+            tree_fs.root.join("templates/model.t"),
+            &json!({"name": "b"}),
+        )
+        .unwrap();
+    assert!(patch.contains("+++ b/src/b.rs\n@@ -0,0 +1 @@\n+fn b() {}\n"));
+    assert!(patch.contains("+++ b/src/lib.rs\n"));
+    assert!(patch.contains("\n+impl Model {}"));
+
+    assert!(!tree_fs.root.join("src/b.rs").exists());
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("src/lib.rs")).unwrap(),
+        "pub struct Model;\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_generate_patch_modes() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("bin/setup", "#!/bin/sh\n")
+        .add("bin/old", "#!/bin/sh\n")
+        .create()
+        .expect("create temp file");
+    fs::set_permissions(
+        tree_fs.root.join("bin/old"),
+        fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+
+    let template = "to: bin/setup\nmode: 0755\ndelete:\n- bin/old\n---\n#!/bin/sh\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root).with_overwrite(Overwrite::Always);
+    let patch = rgen.generate_patch(template, &json!({})).unwrap();
+    assert_eq!(
+        patch,
+        r"diff --git a/bin/old b/bin/old
+deleted file mode 100755
+--- a/bin/old
++++ /dev/null
+@@ -1 +0,0 @@
+-#!/bin/sh
+diff --git a/bin/setup b/bin/setup
+old mode 100644
+new mode 100755
+"
+    );
+}
+
+#[cfg(feature = "git")]
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("run git");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[cfg(feature = "git")]
+#[test]
+fn test_git_clean_check_and_commit() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "mod a;\n")
+        .create()
+        .expect("create temp file");
+    let root = &tree_fs.root;
+    git(root, &["init", "-q"]);
+    git(root, &["config", "user.name", "rrgen"]);
+    git(root, &["config", "user.email", "rrgen@example.com"]);
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+
+    let template = r#"---
+to: src/{{name}}.rs
+injections:
+- into: src/lib.rs
+  append: true
+  content: "mod {{name}};"
+---
+fn {{name}}() {}
+"#;
+    let rgen = RRgen::with_working_dir(root).with_git(Git {
+        require_clean: true,
+        commit: Some("add {{ name }}".to_string()),
+        ..Git::default()
+    });
+
+    fs::write(root.join("src/lib.rs"), "mod a;\nmod wip;\n").unwrap();
+    let err = rgen.generate(template, &json!({"name": "b"})).unwrap_err();
+    assert_eq!(err.to_string(), "uncommitted changes in: src/lib.rs");
+    assert!(!root.join("src/b.rs").exists());
+
+    git(root, &["checkout", "src/lib.rs"]);
+    rgen.generate(template, &json!({"name": "b"})).unwrap();
+    assert_eq!(git(root, &["log", "-1", "--format=%s"]), "add b\n");
+    assert_eq!(git(root, &["status", "--porcelain"]), "");
+    assert_eq!(
+        git(root, &["show", "--name-only", "--format="]),
+        "src/b.rs\nsrc/lib.rs\n"
+    );
+
+    // what the developer had staged is not committed along
+    fs::write(root.join("notes.txt"), "wip").unwrap();
+    git(root, &["add", "notes.txt"]);
+    rgen.generate(template, &json!({"name": "c"})).unwrap();
+    assert_eq!(
+        git(root, &["show", "--name-only", "--format="]),
+        "src/c.rs\nsrc/lib.rs\n"
+    );
+    assert_eq!(git(root, &["status", "--porcelain"]), "A  notes.txt\n");
+
+    // a commit that cannot be signed is refused before writing anything
+    git(root, &["config", "user.name", ""]);
+    let err = rgen.generate(template, &json!({"name": "d"})).unwrap_err();
+    assert!(matches!(err, rrgen::Error::Git(_)), "{err}");
+    assert!(!root.join("src/d.rs").exists());
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn test_archive() {
+    use std::io::Read;
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("src/lib.rs", "mod a;")
+        .create()
+        .expect("create temp file");
+    let model = r#"---
+to: src/{{name}}.rs
+injections:
+- into: src/lib.rs
+  append: true
+  content: "mod {{name}};"
+---
+fn {{name}}() {}
+"#;
+    let script = "to: bin/setup.sh\nmode: 0755\n---\necho setup\n";
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    let generate = |rgen: &RRgen| {
+        rgen.generate(model, &json!({"name": "b"}))?;
+        rgen.generate(model, &json!({"name": "c"}))?;
+        rgen.generate(script, &json!({}))?;
+        Ok(())
+    };
+
+    let zip = rgen.archive(ArchiveFormat::Zip, generate).unwrap();
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(zip)).unwrap();
+    let mut files = vec![];
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        files.push((file.name().to_string(), file.unix_mode(), content));
+    }
+    assert_eq!(
+        files,
+        vec![
+            (
+                "bin/setup.sh".to_string(),
+                Some(0o100_755),
+                "echo setup\n".to_string()
+            ),
+            (
+                "src/b.rs".to_string(),
+                Some(0o100_644),
+                "fn b() {}\n".to_string()
+            ),
+            (
+                "src/c.rs".to_string(),
+                Some(0o100_644),
+                "fn c() {}\n".to_string()
+            ),
+            (
+                "src/lib.rs".to_string(),
+                Some(0o100_644),
+                "mod a;\nmod b;\nmod c;".to_string()
+            ),
+        ]
+    );
+
+    let tar_gz = rgen.archive(ArchiveFormat::TarGz, generate).unwrap();
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(tar_gz.as_slice()));
+    let names = tar
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            (
+                entry.path().unwrap().display().to_string(),
+                entry.header().mode().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        vec![
+            ("bin/setup.sh".to_string(), 0o755),
+            ("src/b.rs".to_string(), 0o644),
+            ("src/c.rs".to_string(), 0o644),
+            ("src/lib.rs".to_string(), 0o644),
+        ]
+    );
+
+    // nothing was written
+    assert!(!tree_fs.root.join("src/b.rs").exists());
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("src/lib.rs")).unwrap(),
+        "mod a;"
+    );
+}
+
+#[cfg(all(unix, feature = "archive"))]
+#[test]
+fn test_archive_keeps_modes_and_working_dir() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .add("app/bin/setup", "#!/bin/sh\n")
+        .create()
+        .expect("create temp file");
+    let setup = tree_fs.root.join("app/bin/setup");
+    fs::set_permissions(&setup, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let inject = "injections:\n- into: bin/setup\n  append: true\n  content: echo {{name}}\n---\n";
+    let rgen = RRgen::with_working_dir(tree_fs.root.join("app"));
+    let tar_gz = rgen
+        .archive(ArchiveFormat::TarGz, |rgen| {
+            rgen.generate(inject, &json!({"name": "setup"}))?;
+            Ok(())
+        })
+        .unwrap();
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(tar_gz.as_slice()));
+    let entries = tar
+        .entries()
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            (
+                entry.path().unwrap().display().to_string(),
+                entry.header().mode().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec![("bin/setup".to_string(), 0o755)]);
+
+    let err = rgen
+        .archive(ArchiveFormat::Zip, |rgen| {
+            rgen.generate("to: ../outside.rs\n---\n", &json!({}))?;
+            Ok(())
+        })
+        .unwrap_err();
+    assert!(
+        err.to_string().ends_with("outside the working dir"),
+        "{err}"
+    );
+    assert!(!tree_fs.root.join("outside.rs").exists());
+}
+
+#[test]
+fn test_inflection_filters() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let template = r#"to: inflections.txt
+---
+{{ "posts" | singular }}
+{{ "categories" | singular }}
+{{ "LineItem" | tableize }}
+{{ "line_items" | classify }}
+{{ "Admin::User" | foreign_key }}
+{{ "employee_salary" | humanize }}
+{{ "author_id" | humanize }}
+{{ 1 | ordinalize }} {{ "22" | ordinalize }} {{ 13 | ordinalize }}
+{{ "Admin::Users::Profile" | demodulize }}
+{{ "Admin::Users::Profile" | deconstantize }}
+{{ "admin/users" | camelize }}
+{{ "admin/api_users" | camelize(acronyms=["API"]) }}
+{{ "html_api_client" | camelize(acronyms=["API", "HTML"]) }}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({})).unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("inflections.txt")).unwrap(),
+        "post
+category
+line_items
+LineItem
+user_id
+Employee salary
+Author
+1st 22nd 13th
+Profile
+Admin::Users
+Admin::Users
+Admin::APIUsers
+HTMLAPIClient
+"
+    );
+}
+
+#[test]
+fn test_case_filters() {
+    let tree_fs = tree_fs::TreeBuilder::default()
+        .drop(true)
+        .create()
+        .expect("create temp file");
+    let template = r#"to: cases.txt
+---
+{{ name | screaming_snake_case }}
+{{ name | train_case }}
+{{ name | shouty_kebab_case }}
+{{ name | dot_case }}
+{{ name | path_case }}
+"#;
+    let rgen = RRgen::with_working_dir(&tree_fs.root);
+    rgen.generate(template, &json!({"name": "EmailStats queue"}))
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(tree_fs.root.join("cases.txt")).unwrap(),
+        "EMAIL_STATS_QUEUE
+Email-Stats-Queue
+EMAIL-STATS-QUEUE
+email.stats.queue
+email/stats/queue
+"
+    );
+}